@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+
+use crate::i18n;
+use crate::text;
+use crate::theme::{self, Themed};
+use crate::{Args, SearchResult};
+
+/// Resolves a capture group given by name or index to its index in `pattern`.
+pub fn resolve_capture(pattern: &Regex, spec: &str) -> Result<usize, String> {
+    if let Ok(index) = spec.parse::<usize>() {
+        if index < pattern.captures_len() {
+            return Ok(index);
+        }
+        return Err(format!(
+            "capture group {} does not exist (pattern has {} groups)",
+            index,
+            pattern.captures_len() - 1
+        ));
+    }
+    pattern
+        .capture_names()
+        .position(|name| name == Some(spec))
+        .ok_or_else(|| format!("pattern has no capture group named '{}'", spec))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Agg {
+    /// Number of matches per group
+    Count,
+    /// Sum of the numeric --capture values
+    Sum,
+    /// Smallest numeric --capture value
+    Min,
+    /// Largest numeric --capture value
+    Max,
+    /// Mean of the numeric --capture values
+    Avg,
+    /// 95th percentile (nearest rank) of the numeric --capture values
+    P95,
+}
+
+impl Agg {
+    /// Reduces `values` to a single number; `values` must not be empty.
+    fn apply(self, values: &mut [f64]) -> f64 {
+        match self {
+            Agg::Count => values.len() as f64,
+            Agg::Sum => values.iter().sum(),
+            Agg::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Agg::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Agg::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Agg::P95 => {
+                values.sort_by(f64::total_cmp);
+                let rank = (0.95 * values.len() as f64).ceil() as usize;
+                values[rank.saturating_sub(1)]
+            }
+        }
+    }
+}
+
+/// A `--sort-by capture:NAME[:numeric]` specification.
+#[derive(Debug, Clone)]
+pub struct SortSpec {
+    pub capture: String,
+    pub numeric: bool,
+}
+
+pub fn parse_sort_spec(spec: &str) -> Result<SortSpec, String> {
+    let rest = spec
+        .strip_prefix("capture:")
+        .ok_or_else(|| format!("expected 'capture:NAME[:numeric]', got '{}'", spec))?;
+    let (capture, numeric) = match rest.rsplit_once(':') {
+        Some((name, "numeric")) => (name, true),
+        _ => (rest, false),
+    };
+    if capture.is_empty() {
+        return Err("missing capture group name".to_string());
+    }
+    Ok(SortSpec { capture: capture.to_string(), numeric })
+}
+
+/// Computes `--sort-by` keys: the value of capture `group` in each line's
+/// first reported match, so under `--scope` or `--select` a match outside
+/// them doesn't decide. Lines where the group doesn't participate (or, when
+/// `numeric`, doesn't parse as a number) sort last.
+pub struct Sorter<'a> {
+    pub pattern: &'a Regex,
+    pub group: usize,
+    pub numeric: bool,
+}
+
+impl Sorter<'_> {
+    pub fn key(&self, result: &SearchResult) -> SortKey {
+        let value = result
+            .matches
+            .first()
+            .and_then(|&span| captures_at_span(self.pattern, &result.line, span))
+            .and_then(|caps| caps.get(self.group).map(|m| m.as_str().to_string()));
+
+        if self.numeric {
+            SortKey::Number(NumericKey(value.and_then(|v| v.trim().parse::<f64>().ok())))
+        } else {
+            SortKey::Text(value.is_none(), value)
+        }
+    }
+
+    /// Stably sorts `results` in memory.
+    pub fn sort(&self, results: &mut [SearchResult]) {
+        results.sort_by_cached_key(|result| self.key(result));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortKey {
+    Text(bool, Option<String>),
+    Number(NumericKey),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NumericKey(Option<f64>);
+
+impl Eq for NumericKey {}
+
+impl PartialOrd for NumericKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NumericKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+    }
+}
+
+/// Calls `f` with the groups of every match `results` report, by index.
+/// Each match is one of `pattern`'s where `pattern` matches at exactly that
+/// span; otherwise (a `--fuzzy` match, say) it only has group 0, the
+/// matched text. Matches outside `--scope` or `--select` aren't reported,
+/// so they aren't counted.
+fn for_each_capture(
+    results: impl IntoIterator<Item = SearchResult>,
+    pattern: &Regex,
+    mut f: impl FnMut(&[Option<&str>]),
+) {
+    for result in results {
+        let mut groups = Vec::new();
+        for &(start, end) in &result.matches {
+            let Some(text) = result.line.get(start..end) else { continue };
+            groups.clear();
+            match captures_at_span(pattern, &result.line, (start, end)) {
+                Some(caps) => groups.extend(caps.iter().map(|m| m.map(|m| m.as_str()))),
+                None => groups.push(Some(text)),
+            }
+            f(&groups);
+        }
+    }
+}
+
+/// The captures of `pattern` matching exactly `span` of `line`, a match the
+/// search reported.
+fn captures_at_span<'a>(pattern: &Regex, line: &'a str, (start, end): (usize, usize)) -> Option<Captures<'a>> {
+    let caps = pattern.captures_at(line, start)?;
+    caps.get(0).is_some_and(|m| (m.start(), m.end()) == (start, end)).then_some(caps)
+}
+
+/// Group `index` of a match passed to `for_each_capture`'s callback.
+fn group<'a>(groups: &[Option<&'a str>], index: usize) -> Option<&'a str> {
+    groups.get(index).copied().flatten()
+}
+
+/// Counts each distinct value of capture `group` across `results`.
+fn count_captures(
+    results: impl IntoIterator<Item = SearchResult>,
+    pattern: &Regex,
+    group: usize,
+) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for_each_capture(results, pattern, |groups| {
+        if let Some(value) = self::group(groups, group) {
+            *counts.entry(value.to_string()).or_insert(0) += 1;
+        }
+    });
+    sort_counts(counts)
+}
+
+/// Orders counts most frequent first, ties broken alphabetically.
+fn sort_counts(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+pub fn display_unique(results: impl IntoIterator<Item = SearchResult>, pattern: &Regex, group: usize, args: &Args) {
+    let counts = count_captures(results, pattern, group);
+
+    let palette = theme::palette();
+    let count = counts.len().to_string().themed(palette.count).to_string();
+    println!("\n{}", i18n::tr("distinct-values", &[("count", &count)]));
+    if args.list_unique {
+        println!();
+        for (value, count) in &counts {
+            println!("{:>8} {}", count.to_string().themed(palette.line_number), value);
+        }
+    }
+}
+
+/// Prints one line per bucket of capture `group_by` (or a single total when
+/// `None`), sorted by the aggregate. Numeric aggregates are computed over
+/// capture `value_group`; values that don't parse as numbers are reported
+/// separately rather than silently dropped.
+pub fn display_grouped(
+    results: impl IntoIterator<Item = SearchResult>,
+    pattern: &Regex,
+    group_by: Option<usize>,
+    value_group: usize,
+    args: &Args,
+) {
+    if args.agg == Agg::Count {
+        if let Some(group_by) = group_by {
+            print_buckets(count_captures(results, pattern, group_by)
+                .into_iter()
+                .map(|(key, count)| (key, count.to_string()))
+                .collect());
+            return;
+        }
+    }
+
+    let mut buckets: HashMap<String, Vec<f64>> = HashMap::new();
+    let mut non_numeric: HashMap<String, usize> = HashMap::new();
+    for_each_capture(results, pattern, |groups| {
+        let key = match group_by {
+            Some(index) => match group(groups, index) {
+                Some(key) => key.to_string(),
+                None => return,
+            },
+            None => String::from("total"),
+        };
+        let Some(value) = group(groups, value_group) else { return };
+        match value.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => buckets.entry(key).or_default().push(number),
+            _ => *non_numeric.entry(value.to_string()).or_insert(0) += 1,
+        }
+    });
+
+    let mut rows: Vec<(String, f64)> = buckets
+        .into_iter()
+        .map(|(key, mut values)| {
+            let aggregate = args.agg.apply(&mut values);
+            (key, aggregate)
+        })
+        .collect();
+    rows.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    print_buckets(rows.into_iter().map(|(key, value)| (key, format_number(value))).collect());
+
+    if !non_numeric.is_empty() {
+        let skipped: usize = non_numeric.values().sum();
+        let distinct = sort_counts(non_numeric);
+        eprintln!(
+            "{}",
+            i18n::tr("non-numeric-skipped", &[("count", &skipped)]).themed(theme::palette().notice)
+        );
+        for (value, count) in distinct.iter().take(5) {
+            eprintln!("{:>8} {}", count, value);
+        }
+    }
+}
+
+fn print_buckets(buckets: Vec<(String, String)>) {
+    if buckets.is_empty() {
+        println!("{}", i18n::tr("no-matches", &[]).themed(theme::palette().notice));
+        return;
+    }
+
+    let width = buckets.iter().map(|(key, _)| text::display_width(key)).max().unwrap_or(0);
+    let palette = theme::palette();
+    for (key, value) in &buckets {
+        let colored_key = key.themed(palette.path).to_string();
+        println!("{}  {}", text::pad_to_width(&colored_key, key, width), value.themed(palette.line_number));
+    }
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(line: &str, matches: Vec<(usize, usize)>) -> SearchResult {
+        SearchResult {
+            file_path: "a.rs".to_string(),
+            line_number: 1,
+            line: line.to_string(),
+            terms: vec![0; matches.len()],
+            matches,
+            byte_offset: None,
+            origin: None,
+            cell: None,
+            context: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn counts_only_reported_matches() {
+        let pattern = Regex::new(r"ERR (\d)").unwrap();
+        // As with --scope comments: only the second match was reported.
+        let results = [result("\"ERR 5\" // ERR 7", vec![(11, 16)])];
+        assert_eq!(count_captures(results, &pattern, 1), [("7".to_string(), 1)]);
+    }
+
+    #[test]
+    fn numeric_aggregates() {
+        let values = [4.0, 1.0, 10.0, 5.0];
+        let apply = |agg: Agg| agg.apply(&mut values.clone());
+        assert_eq!(apply(Agg::Count), 4.0);
+        assert_eq!(apply(Agg::Sum), 20.0);
+        assert_eq!(apply(Agg::Min), 1.0);
+        assert_eq!(apply(Agg::Max), 10.0);
+        assert_eq!(apply(Agg::Avg), 5.0);
+        // Nearest rank: the 4th of 4 values.
+        assert_eq!(apply(Agg::P95), 10.0);
+        let hundred: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(Agg::P95.apply(&mut hundred.clone()), 95.0);
+        assert_eq!((format_number(20.0), format_number(2.5), format_number(-3.0)), ("20".to_string(), "2.500".to_string(), "-3".to_string()));
+    }
+
+    #[test]
+    fn sort_keys_come_from_the_reported_match() {
+        let pattern = Regex::new(r"n=(\d+)").unwrap();
+        let sorter = Sorter { pattern: &pattern, group: 1, numeric: true };
+        let mut results = [result("n=1 // n=30", vec![(7, 11)]), result("n=20", vec![(0, 4)])];
+        sorter.sort(&mut results);
+        assert_eq!(sorter.key(&results[1]), SortKey::Number(NumericKey(Some(30.0))));
+        assert_eq!(results.map(|r| r.line), ["n=20", "n=1 // n=30"]);
+        assert_eq!(sorter.key(&result("n=5", vec![])), SortKey::Number(NumericKey(None)));
+    }
+
+    #[test]
+    fn spans_the_pattern_misses_are_group_zero() {
+        let pattern = Regex::new("color").unwrap();
+        let results = [result("colour and color", vec![(0, 6), (11, 16)])];
+        assert_eq!(count_captures(results.clone(), &pattern, 0), [("color".to_string(), 1), ("colour".to_string(), 1)]);
+        assert!(count_captures(results, &pattern, 1).is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::OnceLock;
+
+use memmap2::Mmap;
+use regex::bytes;
+
+use colored::Colorize;
+
+use crate::encoding::{self, Bom};
+use crate::pattern::Matcher;
+use crate::theme;
+use crate::{Args, SearchResult};
+
+/// Bytes at the start of a file checked for a NUL.
+const SNIFF_LEN: u64 = 8 * 1024;
+/// Longest excerpt of matched bytes kept in a result.
+const EXCERPT_LEN: usize = 64;
+/// Bytes per `--hex-context` row, as in `xxd`.
+const ROW_LEN: usize = 16;
+
+/// Whether `file` looks binary: a NUL byte in its first block, unless it
+/// starts with a UTF-16 byte order mark. Leaves the file at its start.
+pub fn is_binary(file: &mut File) -> io::Result<bool> {
+    let mut head = Vec::new();
+    file.by_ref().take(SNIFF_LEN).read_to_end(&mut head)?;
+    file.seek(SeekFrom::Start(0))?;
+    let utf16 = matches!(encoding::sniff_bom(&head), Some(Bom::Utf16Le | Bom::Utf16Be));
+    Ok(!utf16 && head.contains(&0))
+}
+
+/// Why a binary file wasn't searched: without `--binary` or `-a` it has
+/// neither matches nor non-matches, so `--files-without-match` must not
+/// list it.
+#[derive(Debug)]
+pub struct Skipped;
+
+impl std::fmt::Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("binary file skipped")
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+/// Searches the raw bytes of a binary file. Each match becomes a result
+/// carrying its byte offset, with the matched bytes made printable (as in
+/// `xxd`, anything but printable ASCII shows as `.`) instead of the line.
+pub fn search(file: &File, file_path: &str, pattern: &Matcher, args: &Args) -> io::Result<Vec<SearchResult>> {
+    let Some(regex) = bytes_regex(pattern, args) else { return Ok(Vec::new()) };
+    let terms = term_regexes(pattern, args);
+    let data = unsafe { Mmap::map(file)? };
+
+    let mut results = Vec::new();
+    let mut line_number = 1;
+    let mut counted = 0;
+    for m in regex.find_iter(&data) {
+        line_number += data[counted..m.start()].iter().filter(|&&b| b == b'\n').count();
+        counted = m.start();
+        // The raw bytes tell which term matched; the excerpt may be cut short
+        // or have lost them to '.'.
+        let term = terms.iter().position(|term| term.is_match(&data[m.start()..])).unwrap_or(0);
+        let line = excerpt(&data[m.start()..m.end().min(m.start() + EXCERPT_LEN)]);
+        results.push(SearchResult {
+            file_path: file_path.to_string(),
+            line_number,
+            matches: vec![(0, line.len())],
+            terms: vec![term],
+            line,
+            byte_offset: Some(m.start()),
+            origin: None,
+            cell: None,
+            context: Vec::new(),
+        });
+        if args.stop_at_first_match() {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// The text pattern as a byte regex, built once, ignoring case the same way
+/// the text one does.
+fn bytes_regex(pattern: &Matcher, args: &Args) -> Option<&'static bytes::Regex> {
+    static REGEX: OnceLock<Option<bytes::Regex>> = OnceLock::new();
+    REGEX
+        .get_or_init(|| {
+            bytes::RegexBuilder::new(pattern.regex().as_str())
+                .case_insensitive(!args.case_sensitive)
+                .build()
+                .ok()
+        })
+        .as_ref()
+}
+
+/// Each term as a byte regex anchored at the start, to tell which one the
+/// combined regex chose at a match, the way `Matcher::term_at` does for
+/// text. Empty when there is only one term.
+fn term_regexes(pattern: &Matcher, args: &Args) -> &'static [bytes::Regex] {
+    static TERMS: OnceLock<Vec<bytes::Regex>> = OnceLock::new();
+    TERMS.get_or_init(|| {
+        pattern
+            .term_sources()
+            .map(|source| {
+                bytes::RegexBuilder::new(&format!("^(?:{})", source)).case_insensitive(!args.case_sensitive).build().ok()
+            })
+            .collect::<Option<_>>()
+            .unwrap_or_default()
+    })
+}
+
+/// An `xxd`-style dump of the rows around `len` bytes at `offset` in the
+/// file at `path`, with `context` more bytes on each side and the matched
+/// bytes highlighted.
+pub fn hex_dump(path: &str, offset: usize, len: usize, context: usize) -> io::Result<Vec<String>> {
+    let first = offset.saturating_sub(context) / ROW_LEN * ROW_LEN;
+    let last = (offset + len + context).div_ceil(ROW_LEN) * ROW_LEN;
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(first as u64))?;
+    let mut data = Vec::new();
+    file.take((last - first) as u64).read_to_end(&mut data)?;
+
+    let matched = offset..offset + len;
+    let paint = |position: usize, text: String| match matched.contains(&position) {
+        true => theme::palette().paint_match(text.normal()).to_string(),
+        false => text,
+    };
+    let rows = data
+        .chunks(ROW_LEN)
+        .enumerate()
+        .map(|(row, bytes)| {
+            let start = first + row * ROW_LEN;
+            let mut hex = String::new();
+            let mut ascii = String::new();
+            for i in 0..ROW_LEN {
+                if i % 2 == 0 {
+                    hex.push(' ');
+                }
+                match bytes.get(i) {
+                    Some(&b) => {
+                        hex.push_str(&paint(start + i, format!("{:02x}", b)));
+                        ascii.push_str(&paint(start + i, excerpt(&[b])));
+                    }
+                    None => hex.push_str("  "),
+                }
+            }
+            format!("{:08x}:{}  {}", start, hex, ascii)
+        })
+        .collect();
+    Ok(rows)
+}
+
+fn excerpt(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern;
+    use clap::Parser;
+
+    // The byte regexes are built once per process, so this is the only
+    // test to run a binary search.
+    #[test]
+    fn terms_come_from_the_raw_bytes() {
+        let path = std::env::temp_dir().join(format!("fsearch-test-{}-binary.bin", std::process::id()));
+        let mut data = b"\0q ".to_vec();
+        data.extend([b'a'; 70]);
+        data.extend(b" caf\xc3\xa9");
+        std::fs::write(&path, &data).unwrap();
+        let args = Args::parse_from(["fsearch", "--binary", "-x", "-t", "q", "-t", "a{70}", "-t", "café"]);
+        let pattern = pattern::compile(&args).unwrap();
+        let results = search(&File::open(&path).unwrap(), "a.bin", &pattern, &args).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // The second match is cut to 64 bytes in its excerpt, the third is
+        // shown as "caf..".
+        assert_eq!(results.iter().map(|r| (r.byte_offset, r.terms[0])).collect::<Vec<_>>(), [(Some(1), 0), (Some(3), 1), (Some(74), 2)]);
+        assert_eq!(results[2].line, "caf..");
+    }
+}
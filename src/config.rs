@@ -0,0 +1,352 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+/// Name of the per-project file, looked up from the search directory upward.
+const PROJECT_FILE: &str = ".fsearch.toml";
+
+/// Reads the user config and then the nearest project config above
+/// `directory`, returning their settings as command-line flags. Callers put
+/// these before the real arguments so that flags given on the command line
+/// win, and project settings override user ones the same way. Flags that
+/// take a list, like `--show-meta`, accumulate across all layers instead.
+///
+/// Each `key = value` sets the long flag `--key` (underscores may be used for
+/// dashes): strings and numbers become its value, `true` sets a switch,
+/// `false` leaves it unset, and an array repeats the flag once per element.
+/// For example:
+///
+/// ```toml
+/// extension = "rs"
+/// include_vendored = true
+/// max-line-length = 400
+/// ```
+pub fn load(directory: &str) -> Result<Vec<OsString>, String> {
+    let mut flags = Vec::new();
+    for path in user_config().into_iter().chain(project_config(Path::new(directory))) {
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let entries = parse(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (key, value) in entries {
+            push_flags(&mut flags, &key.replace('_', "-"), &value);
+        }
+    }
+    Ok(flags)
+}
+
+/// Reads `FSEARCH_*` environment variables as command-line flags, meant to
+/// go after config-file flags and before the real arguments:
+///
+/// - `FSEARCH_THREADS` sets `--search-threads`
+/// - `FSEARCH_COLOR` sets `--color` (`auto`, `always` or `never`)
+/// - `FSEARCH_OPTS` holds any other flags, split like a shell would, e.g.
+///   `FSEARCH_OPTS="-e rs --template '{path}:{line}'"`
+pub fn env_flags() -> Result<Vec<OsString>, String> {
+    let mut flags = Vec::new();
+    for (var, flag) in [("FSEARCH_THREADS", "search-threads"), ("FSEARCH_COLOR", "color")] {
+        if let Some(value) = env::var_os(var).filter(|v| !v.is_empty()) {
+            let mut arg = OsString::from(format!("--{}=", flag));
+            arg.push(value);
+            flags.push(arg);
+        }
+    }
+    if let Ok(opts) = env::var("FSEARCH_OPTS") {
+        let words = split_words(&opts).map_err(|e| format!("FSEARCH_OPTS: {}", e))?;
+        flags.extend(words.into_iter().map(OsString::from));
+    }
+    Ok(flags)
+}
+
+/// Splits `s` into words at unquoted whitespace. Single quotes keep text
+/// literally; double quotes and backslashes escape as in a POSIX shell.
+fn split_words(s: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.extend(chars.next()),
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Finds the value of `-d`/`--directory` in raw command-line arguments, so
+/// project config can be located before the full parse.
+pub fn directory_arg(args: &[OsString]) -> String {
+    let mut directory = String::from(".");
+    let mut args = args.iter().skip(1).map(|a| a.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "-d" || arg == "--directory" {
+            if let Some(value) = args.next() {
+                directory = value.into_owned();
+            }
+        } else if let Some(value) = arg.strip_prefix("--directory=") {
+            directory = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("-d").filter(|_| !arg.starts_with("--")) {
+            directory = value.strip_prefix('=').unwrap_or(value).to_string();
+        }
+    }
+    directory
+}
+
+/// `$XDG_CONFIG_HOME/fsearch/config.toml`, `~/.config/fsearch/config.toml`,
+/// or `%APPDATA%\fsearch\config.toml` on Windows.
+fn user_config() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    }?;
+    Some(base.join("fsearch").join("config.toml"))
+}
+
+fn project_config(directory: &Path) -> Option<PathBuf> {
+    let start = fs::canonicalize(directory).ok()?;
+    start.ancestors().map(|dir| dir.join(PROJECT_FILE)).find(|path| path.is_file())
+}
+
+#[derive(Debug)]
+enum Value {
+    Text(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+fn push_flags(flags: &mut Vec<OsString>, key: &str, value: &Value) {
+    match value {
+        Value::Text(text) => flags.push(format!("--{}={}", key, text).into()),
+        Value::Bool(true) => flags.push(format!("--{}", key).into()),
+        Value::Bool(false) => {}
+        Value::Array(values) => {
+            for value in values {
+                push_flags(flags, key, value);
+            }
+        }
+    }
+}
+
+/// Parses the subset of TOML used by config files: top-level `key = value`
+/// pairs whose values are strings, numbers, booleans or arrays of those.
+fn parse(source: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut parser = Parser { chars: source.chars().peekable(), line: 1 };
+    let mut entries = Vec::new();
+
+    loop {
+        parser.skip_blank(true);
+        let Some(&c) = parser.chars.peek() else { break };
+        if c == '[' {
+            return Err(parser.error("tables are not supported; set options at the top level"));
+        }
+        let key = parser.key()?;
+        parser.skip_blank(false);
+        if parser.chars.next() != Some('=') {
+            return Err(parser.error(&format!("expected '=' after '{}'", key)));
+        }
+        parser.skip_blank(false);
+        let value = parser.value()?;
+        parser.skip_blank(false);
+        match parser.chars.peek() {
+            None | Some('\n') => {}
+            Some(_) => return Err(parser.error("expected a newline after the value")),
+        }
+        entries.push((key, value));
+    }
+
+    Ok(entries)
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line, message)
+    }
+
+    /// Skips spaces, tabs and comments, and newlines too when `newlines`.
+    fn skip_blank(&mut self, newlines: bool) {
+        while let Some(&c) = self.chars.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if newlines => self.line += 1,
+                '#' => {
+                    while self.chars.peek().is_some_and(|&c| c != '\n') {
+                        self.chars.next();
+                    }
+                    continue;
+                }
+                _ => break,
+            }
+            self.chars.next();
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        if self.chars.peek() == Some(&'"') {
+            return self.string();
+        }
+        let mut key = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                break;
+            }
+            key.push(c);
+            self.chars.next();
+        }
+        if key.is_empty() {
+            return Err(self.error("expected a key"));
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.chars.peek() {
+            Some('"') | Some('\'') => self.string().map(Value::Text),
+            Some('[') => {
+                self.chars.next();
+                let mut values = Vec::new();
+                loop {
+                    self.skip_blank(true);
+                    if self.chars.peek() == Some(&']') {
+                        self.chars.next();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_blank(true);
+                    match self.chars.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err(self.error("expected ',' or ']' in array")),
+                    }
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '_' | '.')) {
+                        break;
+                    }
+                    word.push(c);
+                    self.chars.next();
+                }
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ if word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') => {
+                        Ok(Value::Text(word.replace('_', "")))
+                    }
+                    _ => Err(self.error("expected a string, number, boolean or array")),
+                }
+            }
+        }
+    }
+
+    /// Parses a basic (`"..."`, with escapes) or literal (`'...'`) string.
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.chars.next().unwrap_or('"');
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => return Ok(text),
+                Some('\\') if quote == '"' => match self.chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some('"') => text.push('"'),
+                    Some('\\') => text.push('\\'),
+                    Some('u') => {
+                        let hex: String = self.chars.by_ref().take(4).collect();
+                        let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                        text.push(c.ok_or_else(|| self.error(&format!("invalid escape '\\u{}'", hex)))?);
+                    }
+                    other => {
+                        let shown = other.map(String::from).unwrap_or_default();
+                        return Err(self.error(&format!("invalid escape '\\{}'", shown)));
+                    }
+                },
+                Some(c) => text.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(source: &str) -> Result<Vec<String>, String> {
+        let mut flags = Vec::new();
+        for (key, value) in parse(source)? {
+            push_flags(&mut flags, &key.replace('_', "-"), &value);
+        }
+        Ok(flags.into_iter().map(|flag| flag.into_string().unwrap()).collect())
+    }
+
+    #[test]
+    fn values_become_flags() {
+        let source = "# defaults\nextension = \"rs\"  # trailing comment\ninclude_vendored = true\nhidden = false\nmax-line-length = 1_000\n";
+        assert_eq!(flags(source).unwrap(), ["--extension=rs", "--include-vendored", "--max-line-length=1000"]);
+    }
+
+    #[test]
+    fn strings_escape_and_literal() {
+        let source = "template = \"{path}\\t\\\"{line}\\\" \\u00e9\"\nglob = '!C:\\tmp\\*'\n";
+        assert_eq!(flags(source).unwrap(), ["--template={path}\t\"{line}\" é", "--glob=!C:\\tmp\\*"]);
+    }
+
+    #[test]
+    fn arrays_repeat_the_flag() {
+        let source = "show-meta = [\n  \"size\", # the size\n  'mtime',\n]\nextension = []\n";
+        assert_eq!(flags(source).unwrap(), ["--show-meta=size", "--show-meta=mtime"]);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(flags("a = 1\n[table]\n").unwrap_err(), "line 2: tables are not supported; set options at the top level");
+        assert_eq!(flags("\n\nkey \"x\"").unwrap_err(), "line 3: expected '=' after 'key'");
+        assert_eq!(flags("key = \"open\n").unwrap_err(), "line 1: unterminated string");
+        assert_eq!(flags("key = 1 2\n").unwrap_err(), "line 1: expected a newline after the value");
+        assert_eq!(flags("key = yes\n").unwrap_err(), "line 1: expected a string, number, boolean or array");
+        assert_eq!(flags("key = \"\\q\"\n").unwrap_err(), "line 1: invalid escape '\\q'");
+    }
+}
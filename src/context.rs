@@ -0,0 +1,147 @@
+use std::ops::Range;
+
+use crate::output::Format;
+use crate::{Args, SearchResult};
+
+/// Blocks longer than this are cut down to a window around the match.
+const MAX_BLOCK_LINES: usize = 200;
+
+/// Keywords (after visibility and similar modifiers) that start a function,
+/// type or module definition in common languages.
+const DEFINITION_KEYWORDS: &[&str] = &[
+    "fn ", "def ", "class ", "function", "func ", "impl", "struct ", "enum ", "trait ", "interface ",
+    "module ", "mod ", "sub ", "namespace ", "object ", "union ",
+];
+const MODIFIERS: &[&str] = &[
+    "pub ", "pub(crate) ", "async ", "export ", "default ", "public ", "private ", "protected ", "static ",
+    "final ", "abstract ", "override ", "virtual ", "inline ", "unsafe ", "const ",
+];
+/// Lines starting with these open a block but aren't definitions.
+const CONTROL_KEYWORDS: &[&str] = &[
+    "if", "else", "for", "while", "loop", "match", "switch", "case", "catch", "try", "do", "return", "with",
+];
+
+/// The `-B` and `-A` line counts, if context lines were asked for.
+pub fn lines_around(args: &Args) -> Option<(usize, usize)> {
+    if args.before_context.is_none() && args.after_context.is_none() && args.context.is_none() {
+        return None;
+    }
+    let both = args.context.unwrap_or(0);
+    Some((args.before_context.unwrap_or(both), args.after_context.unwrap_or(both)))
+}
+
+/// Whether results are printed with context lines (`--context-block` or
+/// `-A`/`-B`/`-C` in plain text output), so searches have to keep them.
+pub fn wanted(args: &Args) -> bool {
+    let plain = args.format == Format::Text && args.template.is_none() && args.fields.is_none();
+    let listing = args.count || args.files_with_matches || args.files_without_match || args.count_only_dirs;
+    plain && !listing && (args.context_block || lines_around(args).is_some())
+}
+
+/// How many lines past a match its context can reach, `None` meaning up to
+/// the end of the text (an enclosing block can).
+pub fn reach_after(args: &Args) -> Option<usize> {
+    lines_around(args).map(|(_, after)| after)
+}
+
+/// Gives each result its context lines, taken from the `lines` of the text
+/// it was found in (decoded, so they match the result's own line).
+pub fn attach<S: AsRef<str>>(results: &mut [SearchResult], lines: &[S], args: &Args) {
+    if !wanted(args) {
+        return;
+    }
+    let around = lines_around(args);
+    for result in results.iter_mut().filter(|result| result.byte_offset.is_none() && result.line_number > 0) {
+        let index = result.line_number - 1;
+        let range = match around {
+            Some((before, after)) => index.saturating_sub(before)..index + after + 1,
+            None => enclosing_block(lines, index),
+        };
+        result.context = range
+            .filter(|&i| i != index)
+            .filter_map(|i| Some((i + 1, lines.get(i)?.as_ref().to_string())))
+            .collect();
+    }
+}
+
+/// `attach` for results found in `content`.
+pub fn attach_content(results: &mut [SearchResult], content: &str, args: &Args) {
+    if wanted(args) && !results.is_empty() {
+        attach(results, &content.lines().collect::<Vec<_>>(), args);
+    }
+}
+
+/// Finds the block around line `target` (zero-based) using indentation: the
+/// innermost enclosing line that looks like a definition, or the outermost
+/// enclosing line when none does, through the end of its body including a
+/// closing brace or `end`. Returns a range of line indices.
+pub fn enclosing_block<S: AsRef<str>>(lines: &[S], target: usize) -> Range<usize> {
+    let Some(line) = lines.get(target).map(AsRef::as_ref) else { return target..target + 1 };
+
+    let mut headers = Vec::new();
+    if is_definition(line) {
+        headers.push(target);
+    }
+    let mut level = indent(line);
+    for i in (0..target).rev() {
+        if level == 0 {
+            break;
+        }
+        if lines[i].as_ref().trim().is_empty() {
+            continue;
+        }
+        if indent(lines[i].as_ref()) < level {
+            level = indent(lines[i].as_ref());
+            headers.push(i);
+        }
+    }
+
+    let Some(&start) = headers.iter().find(|&&i| is_definition(lines[i].as_ref())).or(headers.last()) else {
+        return target..target + 1;
+    };
+
+    let header_level = indent(lines[start].as_ref());
+    let mut end = target + 1;
+    for (i, line) in lines.iter().map(AsRef::as_ref).enumerate().skip(target + 1) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if indent(line) <= header_level {
+            if trimmed.starts_with(['}', ')', ']']) || trimmed == "end" || trimmed.starts_with("end ") {
+                end = i + 1;
+            }
+            break;
+        }
+        end = i + 1;
+    }
+
+    if end - start > MAX_BLOCK_LINES {
+        let half = MAX_BLOCK_LINES / 2;
+        let first = target.saturating_sub(half).max(start);
+        return first..(first + MAX_BLOCK_LINES).min(end);
+    }
+    start..end
+}
+
+/// Leading whitespace width, counting a tab as four columns.
+fn indent(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn is_definition(line: &str) -> bool {
+    let mut rest = line.trim_start();
+    while let Some(stripped) = MODIFIERS.iter().find_map(|m| rest.strip_prefix(m)) {
+        rest = stripped;
+    }
+    if DEFINITION_KEYWORDS.iter().any(|k| rest.starts_with(k)) {
+        return true;
+    }
+    // C-like function or method signatures: `int main(void) {`.
+    let word: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    let opens_body = rest.trim_end().ends_with(['{', ')', ':']);
+    rest.contains('(') && opens_body && !word.is_empty() && !CONTROL_KEYWORDS.contains(&word.as_str())
+}
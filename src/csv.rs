@@ -0,0 +1,93 @@
+use crate::text;
+use crate::SearchResult;
+
+/// Column names of the rows written by `rows`.
+pub const HEADER: [&str; 4] = ["path", "line", "column", "match"];
+
+/// A delimited text dialect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    /// Comma separated, fields quoted as in RFC 4180 when needed
+    Csv,
+    /// Tab separated, with tabs, newlines and backslashes escaped
+    Tsv,
+}
+
+/// Encodes one record, without the line terminator.
+pub fn record<'a>(fields: impl IntoIterator<Item = &'a str>, dialect: Dialect) -> String {
+    let fields: Vec<String> = fields.into_iter().map(|field| escape(field, dialect)).collect();
+    match dialect {
+        Dialect::Csv => fields.join(","),
+        Dialect::Tsv => fields.join("\t"),
+    }
+}
+
+/// One record per match of `result`: path, line number, one-based
+/// character column and the matched text.
+pub fn rows(result: &SearchResult, dialect: Dialect) -> Vec<String> {
+    let line_number = result.line_number.to_string();
+    result
+        .spans()
+        .iter()
+        .map(|&(start, end)| {
+            let (start, end, char_start, _) = text::char_span(&result.line, start, end);
+            let column = (char_start + 1).to_string();
+            record([result.file_path.as_str(), &line_number, &column, &result.line[start..end]], dialect)
+        })
+        .collect()
+}
+
+fn escape(field: &str, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::Csv if field.contains([',', '"', '\n', '\r']) => format!("\"{}\"", field.replace('"', "\"\"")),
+        Dialect::Csv => field.to_string(),
+        Dialect::Tsv => {
+            let mut out = String::with_capacity(field.len());
+            for c in field.chars() {
+                match c {
+                    '\t' => out.push_str("\\t"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\\' => out.push_str("\\\\"),
+                    c => out.push(c),
+                }
+            }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_only_when_needed() {
+        assert_eq!(record(["a.rs", "plain"], Dialect::Csv), "a.rs,plain");
+        assert_eq!(record(["say \"hi\"", "a,b"], Dialect::Csv), "\"say \"\"hi\"\"\",\"a,b\"");
+        assert_eq!(record(["two\nlines", "cr\r"], Dialect::Csv), "\"two\nlines\",\"cr\r\"");
+        assert_eq!(record(["", "x"], Dialect::Csv), ",x");
+    }
+
+    #[test]
+    fn tsv_escapes_separators() {
+        assert_eq!(record(["a\tb", "c\nd\re", "back\\slash"], Dialect::Tsv), "a\\tb\tc\\nd\\re\tback\\\\slash");
+        assert_eq!(record(["say \"hi\"", "a,b"], Dialect::Tsv), "say \"hi\"\ta,b");
+    }
+
+    #[test]
+    fn rows_count_columns_in_characters() {
+        let result = SearchResult {
+            file_path: "dir,1/a.txt".to_string(),
+            line_number: 3,
+            line: "é \"foo\" foo".to_string(),
+            matches: vec![(4, 7), (9, 12)],
+            byte_offset: None,
+            terms: vec![0, 0],
+            origin: None,
+            cell: None,
+            context: Vec::new(),
+        };
+        assert_eq!(rows(&result, Dialect::Csv), ["\"dir,1/a.txt\",3,4,foo", "\"dir,1/a.txt\",3,9,foo"]);
+    }
+}
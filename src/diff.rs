@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use colored::Colorize;
+
+use crate::replace::{Change, Rewrite};
+
+/// Unchanged lines shown around each change, as in `diff -u`.
+const CONTEXT: usize = 3;
+
+/// A unified diff of `rewrite`, which `patch -p0` applies to the file.
+/// Changes close enough to share context lines go in one hunk.
+pub fn unified(rewrite: &Rewrite) -> String {
+    let lines: Vec<&str> = rewrite.original.split_inclusive('\n').collect();
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", format!("--- {}", rewrite.path).bold());
+    let _ = writeln!(out, "{}", format!("+++ {}", rewrite.path).bold());
+
+    // How many more lines the new file has before the current hunk.
+    let mut shift: isize = 0;
+    let mut changes = rewrite.changes.as_slice();
+    while let Some(first) = changes.first() {
+        let mut count = 1;
+        while count < changes.len() && changes[count].index - changes[count - 1].index <= 2 * CONTEXT + 1 {
+            count += 1;
+        }
+        let (hunk, rest) = changes.split_at(count);
+        changes = rest;
+
+        let start = first.index.saturating_sub(CONTEXT);
+        let end = (hunk[hunk.len() - 1].index + CONTEXT + 1).min(lines.len());
+        let added: usize = hunk.iter().map(|change| change.text.split_inclusive('\n').count()).sum();
+        let old_len = end - start;
+        let new_len = old_len - hunk.len() + added;
+        let header = format!("@@ -{},{} +{},{} @@", start + 1, old_len, (start + 1) as isize + shift, new_len);
+        let _ = writeln!(out, "{}", header.cyan());
+        shift += new_len as isize - old_len as isize;
+
+        let mut hunk = hunk.iter().peekable();
+        for (index, line) in lines.iter().enumerate().take(end).skip(start) {
+            match hunk.next_if(|change| change.index == index) {
+                Some(Change { text, .. }) => {
+                    push_line(&mut out, '-', line);
+                    text.split_inclusive('\n').for_each(|line| push_line(&mut out, '+', line));
+                }
+                None => push_line(&mut out, ' ', line),
+            }
+        }
+    }
+    out
+}
+
+/// Adds one diff line, marking a last line that has no newline the way
+/// `diff` does.
+fn push_line(out: &mut String, marker: char, line: &str) {
+    let text = format!("{}{}", marker, line.strip_suffix('\n').unwrap_or(line));
+    let text = match marker {
+        '-' => text.red().to_string(),
+        '+' => text.green().to_string(),
+        _ => text,
+    };
+    out.push_str(&text);
+    out.push('\n');
+    if !line.ends_with('\n') {
+        out.push_str("\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The diff of `changes` to `original`, without colors (another test
+    /// may have forced them on).
+    fn diff(original: &str, changes: &[(usize, &str)]) -> String {
+        let changes = changes.iter().map(|&(index, text)| Change { index, text: text.to_string() }).collect();
+        let rewrite = Rewrite {
+            path: "a.txt".to_string(),
+            original: original.to_string(),
+            replaced: String::new(),
+            changes,
+            occurrences: 1,
+        };
+        let mut plain = String::new();
+        let mut escape = false;
+        for c in unified(&rewrite).chars() {
+            match c {
+                '\x1b' => escape = true,
+                'm' if escape => escape = false,
+                _ if escape => {}
+                c => plain.push(c),
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn headers_count_added_lines() {
+        let original: String = (1..=10).map(|n| format!("{}\n", n)).collect();
+        let out = diff(&original, &[(0, "a\nb\n"), (9, "ten\n")]);
+        let headers: Vec<&str> = out.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, ["@@ -1,4 +1,5 @@", "@@ -7,4 +8,4 @@"]);
+        let merged = diff(&original, &[(2, "c\n"), (6, "g\n")]);
+        assert_eq!(merged.lines().filter(|line| line.starts_with("@@")).collect::<Vec<_>>(), ["@@ -1,10 +1,10 @@"]);
+    }
+
+    #[test]
+    fn lines_added_at_the_end() {
+        let out = diff("a\nfoo", &[(1, "bar\nbaz")]);
+        let expected = "--- a.txt\n+++ a.txt\n@@ -1,2 +1,3 @@\n a\n-foo\n\\ No newline at end of file\n+bar\n+baz\n\\ No newline at end of file\n";
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt::Write;
+
+use clap::builder::PossibleValue;
+use clap::{Arg, Command};
+
+/// One-line summary used in the man page NAME section.
+const SUMMARY: &str = "search file contents for a term or regular expression";
+
+/// Extra man page sections that aren't derived from the option definitions.
+const ENVIRONMENT: &[(&str, &str)] = &[
+    ("FSEARCH_THREADS", "Default for --search-threads."),
+    ("FSEARCH_COLOR", "Default for --color."),
+    ("FSEARCH_OPTS", "Extra default flags, split like a shell command line."),
+    ("LC_ALL, LC_MESSAGES, LANG", "Language of messages (English, German and Spanish are available)."),
+];
+
+const FILES: &[(&str, &str)] = &[
+    (
+        "~/.config/fsearch/config.toml",
+        "User defaults, as 'flag = value' lines ($XDG_CONFIG_HOME is honored).",
+    ),
+    (
+        ".fsearch.toml",
+        "Project defaults, found in the search directory or the nearest parent; overrides user defaults.",
+    ),
+];
+
+/// Renders a roff man page for `cmd`.
+pub fn man_page(mut cmd: Command) -> String {
+    cmd.build();
+    let name = cmd.get_name().to_string();
+    let version = cmd.get_version().unwrap_or_default().to_string();
+    let mut page = String::new();
+
+    let _ = writeln!(page, ".TH {} 1 \"\" \"{} {}\"", name.to_uppercase(), name, version);
+    let _ = writeln!(page, ".SH NAME\n{} \\- {}", name, roff(SUMMARY));
+
+    let _ = writeln!(page, ".SH SYNOPSIS");
+    let required: Vec<String> = cmd
+        .get_arguments()
+        .filter(|arg| arg.is_required_set())
+        .map(|arg| format!("{} {}", bold(&flag_names(arg)[0]), italic(&value_name(arg).unwrap_or_default())))
+        .collect();
+    let _ = writeln!(page, "{} [OPTIONS] {}\n.br", bold(&name), required.join(" "));
+    let _ = writeln!(page, "{} gen-man\n.br", bold(&name));
+    let _ = writeln!(page, "{} explain {}", bold(&name), italic("FLAG"));
+
+    let _ = writeln!(page, ".SH DESCRIPTION");
+    let _ = writeln!(
+        page,
+        "{}",
+        roff(
+            "Searches the files under a directory for a term (or, with --regex, a regular expression) \
+             and prints each matching line. Options can also be set in config files and environment \
+             variables; see FILES and ENVIRONMENT. \"fsearch explain FLAG\" describes a single option."
+        )
+    );
+
+    let _ = writeln!(page, ".SH OPTIONS");
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let mut names: Vec<String> = flag_names(arg).iter().map(|n| bold(n)).collect();
+        if let Some(value) = value_name(arg) {
+            let last = names.len() - 1;
+            names[last] = format!("{} {}", names[last], italic(&value));
+        }
+        let _ = writeln!(page, ".TP\n{}", names.join(", "));
+        let _ = writeln!(page, "{}", roff(&help(arg)));
+        if let Some(default) = default_value(arg) {
+            let _ = writeln!(page, ".br\nDefault: {}", roff(&default));
+        }
+        let values = possible_values(arg);
+        if !values.is_empty() {
+            let _ = writeln!(page, ".RS");
+            for value in values {
+                let _ = writeln!(page, ".TP\n{}", bold(value.get_name()));
+                let _ = writeln!(page, "{}", roff(&value.get_help().map(|h| h.to_string()).unwrap_or_default()));
+            }
+            let _ = writeln!(page, ".RE");
+        }
+    }
+
+    for (title, entries) in [("ENVIRONMENT", ENVIRONMENT), ("FILES", FILES)] {
+        let _ = writeln!(page, ".SH {}", title);
+        for (key, text) in entries {
+            let _ = writeln!(page, ".TP\n{}\n{}", bold(key), roff(text));
+        }
+    }
+
+    page
+}
+
+/// Describes the option `flag` (`--name`, `-n` or a bare `name`), or
+/// returns `None` if `cmd` has no such option.
+pub fn explain(mut cmd: Command, flag: &str) -> Option<String> {
+    cmd.build();
+    let wanted = flag.trim_start_matches('-');
+    let arg = cmd.get_arguments().find(|arg| {
+        arg.get_long() == Some(wanted)
+            || arg.get_short().is_some_and(|s| wanted.len() == s.len_utf8() && wanted.starts_with(s))
+            || arg.get_visible_aliases().unwrap_or_default().contains(&wanted)
+            || arg.get_id() == wanted
+    })?;
+
+    let mut out = String::new();
+    let mut title = flag_names(arg).join(", ");
+    if let Some(value) = value_name(arg) {
+        let _ = write!(title, " <{}>", value);
+    }
+    let _ = writeln!(out, "{}\n", title);
+    for line in help(arg).lines() {
+        let _ = writeln!(out, "    {}", line);
+    }
+
+    if let Some(default) = default_value(arg) {
+        let _ = writeln!(out, "\n    Default: {}", default);
+    }
+    let values = possible_values(arg);
+    if !values.is_empty() {
+        let _ = writeln!(out, "\n    Values:");
+        let width = values.iter().map(|v| v.get_name().len()).max().unwrap_or(0);
+        for value in &values {
+            let help = value.get_help().map(|h| h.to_string()).unwrap_or_default();
+            let _ = writeln!(out, "      {:<width$}  {}", value.get_name(), help, width = width);
+        }
+    }
+    if let Some(long) = arg.get_long().filter(|&l| l != "help" && l != "version") {
+        let example = if arg.get_action().takes_values() { "VALUE" } else { "true" };
+        let _ = writeln!(out, "\n    In .fsearch.toml or config.toml: {} = {}", long, example);
+    }
+    Some(out)
+}
+
+/// `--long` first, then `-s` and visible aliases.
+fn flag_names(arg: &Arg) -> Vec<String> {
+    let mut names = Vec::new();
+    names.extend(arg.get_long().map(|l| format!("--{}", l)));
+    names.extend(arg.get_short().map(|s| format!("-{}", s)));
+    for alias in arg.get_visible_aliases().unwrap_or_default() {
+        names.push(format!("--{}", alias));
+    }
+    names
+}
+
+fn value_name(arg: &Arg) -> Option<String> {
+    if !arg.get_action().takes_values() {
+        return None;
+    }
+    Some(match arg.get_value_names() {
+        Some(names) => names.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(" "),
+        None => arg.get_id().as_str().to_uppercase(),
+    })
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_long_help().or(arg.get_help()).map(|h| h.to_string()).unwrap_or_default()
+}
+
+fn default_value(arg: &Arg) -> Option<String> {
+    if !arg.get_action().takes_values() {
+        return None;
+    }
+    let defaults: Vec<_> = arg.get_default_values().iter().map(|v| v.to_string_lossy()).collect();
+    (!defaults.is_empty()).then(|| defaults.join(","))
+}
+
+fn possible_values(arg: &Arg) -> Vec<PossibleValue> {
+    if !arg.get_action().takes_values() {
+        return Vec::new();
+    }
+    arg.get_possible_values().into_iter().filter(|v| !v.is_hide_set()).collect()
+}
+
+/// Escapes text for roff: backslashes and dashes, and a leading control
+/// character on any line.
+fn roff(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn bold(text: &str) -> String {
+    format!("\\fB{}\\fR", roff(text))
+}
+
+fn italic(text: &str) -> String {
+    format!("\\fI{}\\fR", roff(text))
+}
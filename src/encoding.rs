@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+use std::io;
+
+use clap::ValueEnum;
+
+/// What to do with file content that is not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum EncodingErrors {
+    /// Skip the file without reporting it
+    Skip,
+    /// Replace invalid sequences with U+FFFD and search the result
+    Lossy,
+    /// Report the file as an error
+    Strict,
+}
+
+/// Decodes `bytes`, which start at byte `offset` of `file_path`, according to
+/// `policy`. Returns `Ok(None)` when the file should be skipped.
+pub fn decode<'a>(
+    bytes: &'a [u8],
+    offset: usize,
+    file_path: &str,
+    policy: EncodingErrors,
+) -> io::Result<Option<Cow<'a, str>>> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(Some(Cow::Borrowed(text))),
+        Err(_) if policy == EncodingErrors::Lossy => Ok(Some(String::from_utf8_lossy(bytes))),
+        Err(_) if policy == EncodingErrors::Skip => Ok(None),
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: invalid UTF-8 at byte {}", file_path, offset + e.valid_up_to()),
+        )),
+    }
+}
+
+/// A byte order mark found at the start of a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bom {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Bom {
+    pub fn len(self) -> usize {
+        match self {
+            Bom::Utf8 => 3,
+            Bom::Utf16Le | Bom::Utf16Be => 2,
+        }
+    }
+}
+
+pub fn sniff_bom(bytes: &[u8]) -> Option<Bom> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some(Bom::Utf8)
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        Some(Bom::Utf16Le)
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        Some(Bom::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Transcodes UTF-16 `bytes` (without the BOM) to UTF-8, applying `policy`
+/// to unpaired surrogates and a dangling odd byte.
+pub fn decode_utf16(
+    bytes: &[u8],
+    bom: Bom,
+    file_path: &str,
+    policy: EncodingErrors,
+) -> io::Result<Option<String>> {
+    let units = bytes.chunks_exact(2).map(|pair| match bom {
+        Bom::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]]),
+    });
+
+    let mut text = String::with_capacity(bytes.len() / 2);
+    let mut unit_offset = 0;
+    let mut invalid_at = None;
+
+    for decoded in char::decode_utf16(units) {
+        match decoded {
+            Ok(c) => {
+                text.push(c);
+                unit_offset += c.len_utf16();
+            }
+            Err(_) => {
+                invalid_at.get_or_insert(unit_offset * 2);
+                text.push(char::REPLACEMENT_CHARACTER);
+                unit_offset += 1;
+            }
+        }
+    }
+    if bytes.len() % 2 == 1 {
+        invalid_at.get_or_insert(bytes.len() - 1);
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+
+    match (invalid_at, policy) {
+        (None, _) | (Some(_), EncodingErrors::Lossy) => Ok(Some(text)),
+        (Some(_), EncodingErrors::Skip) => Ok(None),
+        (Some(offset), EncodingErrors::Strict) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: invalid UTF-16 at byte {}", file_path, bom.len() + offset),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_utf8_follows_the_policy() {
+        let bytes = b"ok \xff here";
+        assert_eq!(decode(b"fine", 0, "a", EncodingErrors::Strict).unwrap().as_deref(), Some("fine"));
+        assert_eq!(decode(bytes, 0, "a", EncodingErrors::Lossy).unwrap().as_deref(), Some("ok \u{fffd} here"));
+        assert!(decode(bytes, 0, "a", EncodingErrors::Skip).unwrap().is_none());
+        // Errors give the position in the file, past what came before.
+        let error = decode(bytes, 100, "a.txt", EncodingErrors::Strict).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "a.txt: invalid UTF-8 at byte 103");
+    }
+
+    #[test]
+    fn boms_are_sniffed() {
+        assert_eq!(sniff_bom(b"\xef\xbb\xbfabc"), Some(Bom::Utf8));
+        assert_eq!(sniff_bom(b"\xff\xfea\0"), Some(Bom::Utf16Le));
+        assert_eq!(sniff_bom(b"\xfe\xff\0a"), Some(Bom::Utf16Be));
+        assert_eq!(sniff_bom(b"\xef\xbb"), None);
+        assert_eq!(sniff_bom(b"abc"), None);
+    }
+
+    #[test]
+    fn utf16_transcodes_by_byte_order() {
+        let text = "h\u{e9} \u{1f600}";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode_utf16(&le, Bom::Utf16Le, "a", EncodingErrors::Strict).unwrap().as_deref(), Some(text));
+        assert_eq!(decode_utf16(&be, Bom::Utf16Be, "a", EncodingErrors::Strict).unwrap().as_deref(), Some(text));
+    }
+
+    #[test]
+    fn broken_utf16_follows_the_policy() {
+        // "a", an unpaired high surrogate, "b", then a dangling byte.
+        let bytes = [b'a', 0, 0x00, 0xd8, b'b', 0, b'c'];
+        let lossy = decode_utf16(&bytes, Bom::Utf16Le, "a", EncodingErrors::Lossy).unwrap();
+        assert_eq!(lossy.as_deref(), Some("a\u{fffd}b\u{fffd}"));
+        assert!(decode_utf16(&bytes, Bom::Utf16Le, "a", EncodingErrors::Skip).unwrap().is_none());
+        // The offset counts the BOM before the bytes.
+        let error = decode_utf16(&bytes, Bom::Utf16Le, "a.txt", EncodingErrors::Strict).unwrap_err();
+        assert_eq!(error.to_string(), "a.txt: invalid UTF-16 at byte 4");
+    }
+}
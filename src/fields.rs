@@ -0,0 +1,95 @@
+/// A `--fields 'SEP:LIST'` specification. An empty separator splits on runs
+/// of whitespace like awk's default field splitting.
+#[derive(Debug, Clone)]
+pub struct FieldSpec {
+    separator: String,
+    /// Inclusive 1-based ranges of fields to print.
+    ranges: Vec<(usize, usize)>,
+}
+
+pub fn parse_field_spec(spec: &str) -> Result<FieldSpec, String> {
+    let (separator, list) = spec
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected 'SEP:LIST' (e.g. ',:1,3'), got '{}'", spec))?;
+
+    let separator = match separator {
+        "\\t" => "\t".to_string(),
+        " " => String::new(),
+        other => other.to_string(),
+    };
+
+    let mut ranges = Vec::new();
+    for item in list.split(',') {
+        let parse = |s: &str| match s.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("invalid field number '{}' (fields start at 1)", s)),
+        };
+        let range = match item.split_once('-') {
+            Some((from, to)) => (parse(from)?, parse(to)?),
+            None => {
+                let n = parse(item)?;
+                (n, n)
+            }
+        };
+        if range.0 > range.1 {
+            return Err(format!("invalid field range '{}'", item));
+        }
+        ranges.push(range);
+    }
+
+    Ok(FieldSpec { separator, ranges })
+}
+
+impl FieldSpec {
+    /// Returns the selected fields of `line`, joined by the separator (or a
+    /// single space in whitespace mode). Missing fields are omitted.
+    pub fn extract(&self, line: &str) -> String {
+        let fields: Vec<&str> = if self.separator.is_empty() {
+            line.split_whitespace().collect()
+        } else {
+            line.split(self.separator.as_str()).collect()
+        };
+
+        let mut selected = Vec::new();
+        for &(from, to) in &self.ranges {
+            for n in from..=to.min(fields.len()) {
+                selected.push(fields[n - 1]);
+            }
+        }
+
+        let joiner = if self.separator.is_empty() { " " } else { self.separator.as_str() };
+        selected.join(joiner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(spec: &str, line: &str) -> String {
+        parse_field_spec(spec).unwrap().extract(line)
+    }
+
+    #[test]
+    fn selects_fields_and_ranges() {
+        assert_eq!(extract(",:1,3", "a,b,c,d"), "a,c");
+        assert_eq!(extract(",:2-3", "a,b,c,d"), "b,c");
+        assert_eq!(extract("\\t:2", "a\tb\tc"), "b");
+        assert_eq!(extract("::2", "a:b:c"), "b");
+        // Fields past the end are left out.
+        assert_eq!(extract(",:3-9", "a,b,c,d"), "c,d");
+    }
+
+    #[test]
+    fn whitespace_splits_like_awk() {
+        assert_eq!(extract(" :1,3", "  GET   /index  200 "), "GET 200");
+    }
+
+    #[test]
+    fn rejects_bad_specs() {
+        assert!(parse_field_spec("1,2").is_err());
+        assert!(parse_field_spec(",:0").is_err());
+        assert!(parse_field_spec(",:3-1").is_err());
+        assert!(parse_field_spec(",:x").is_err());
+    }
+}
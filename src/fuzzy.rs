@@ -0,0 +1,138 @@
+/// Finds substrings of a line within a given edit distance of a term
+/// (insertions, deletions, substitutions and swaps of adjacent characters),
+/// using Sellers' dynamic programming algorithm: O(term length × line
+/// length) per line.
+pub struct Fuzzy {
+    term: Vec<char>,
+    distance: usize,
+    ignore_case: bool,
+}
+
+/// Cost so far and where in the line the candidate match started.
+#[derive(Clone, Copy)]
+struct Cell {
+    cost: usize,
+    start: usize,
+}
+
+impl Fuzzy {
+    pub fn new(term: &str, distance: usize, ignore_case: bool) -> Result<Fuzzy, String> {
+        let term: Vec<char> = term.chars().map(|c| if ignore_case { fold(c) } else { c }).collect();
+        if term.len() <= distance {
+            return Err(format!(
+                "'{}' is too short for --fuzzy {}: any {} characters would match",
+                term.iter().collect::<String>(),
+                distance,
+                term.len()
+            ));
+        }
+        Ok(Fuzzy { term, distance, ignore_case })
+    }
+
+    /// Non-overlapping approximate matches in `line`, as byte spans.
+    pub fn find_iter(&self, line: &str) -> Vec<(usize, usize)> {
+        let chars: Vec<char> = line.chars().map(|c| if self.ignore_case { fold(c) } else { c }).collect();
+        let offsets: Vec<usize> = line.char_indices().map(|(offset, _)| offset).chain([line.len()]).collect();
+        let mut spans = Vec::new();
+        let mut from = 0;
+        while let Some((start, end)) = self.find_from(&chars, from) {
+            spans.push((offsets[start], offsets[end]));
+            from = end;
+        }
+        spans
+    }
+
+    /// The first match starting at or after character `from`, as character
+    /// indices. A match ends where the distance is lowest, so `recieve` in
+    /// `receives` covers `receive` rather than stopping early at `receiv`.
+    fn find_from(&self, chars: &[char], from: usize) -> Option<(usize, usize)> {
+        let mut before = self.fresh_column(from);
+        let mut column = self.fresh_column(from);
+        let mut best: Option<(Cell, usize)> = None;
+
+        for (j, &c) in chars.iter().enumerate().skip(from) {
+            let mut next = Vec::with_capacity(column.len());
+            next.push(Cell { cost: 0, start: j + 1 });
+            for i in 1..=self.term.len() {
+                let substitute = column[i - 1].cost + usize::from(self.term[i - 1] != c);
+                let skip_line = column[i].cost + 1;
+                let skip_term = next[i - 1].cost + 1;
+                let mut cell = if substitute <= skip_line && substitute <= skip_term {
+                    Cell { cost: substitute, start: column[i - 1].start }
+                } else if skip_line <= skip_term {
+                    Cell { cost: skip_line, start: column[i].start }
+                } else {
+                    Cell { cost: skip_term, start: next[i - 1].start }
+                };
+                let swapped = i >= 2 && j > from && self.term[i - 1] == chars[j - 1] && self.term[i - 2] == c;
+                if swapped && before[i - 2].cost + 1 < cell.cost {
+                    cell = Cell { cost: before[i - 2].cost + 1, start: before[i - 2].start };
+                }
+                next.push(cell);
+            }
+            before = std::mem::replace(&mut column, next);
+
+            let end = column[self.term.len()];
+            match best {
+                Some((found, _)) if end.cost < found.cost => best = Some((end, j + 1)),
+                Some(_) => break,
+                None if end.cost <= self.distance => best = Some((end, j + 1)),
+                None => {}
+            }
+        }
+        best.map(|(found, end)| (found.start, end))
+    }
+
+    /// The DP column before any line characters, starting at `start`.
+    fn fresh_column(&self, start: usize) -> Vec<Cell> {
+        (0..=self.term.len()).map(|i| Cell { cost: i, start }).collect()
+    }
+}
+
+/// A single-character lowercase form of `c`, so comparing folded characters
+/// ignores case without changing how many characters a line has.
+fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(term: &str, distance: usize, line: &str) -> Vec<(usize, usize)> {
+        Fuzzy::new(term, distance, true).unwrap().find_iter(line)
+    }
+
+    #[test]
+    fn counts_each_kind_of_edit() {
+        assert_eq!(spans("color", 1, "the calor"), vec![(4, 9)]);
+        assert_eq!(spans("colour", 1, "the color."), vec![(4, 9)]);
+        assert_eq!(spans("color", 1, "the colr."), vec![(4, 8)]);
+        assert_eq!(spans("color", 1, "my colxor"), vec![(3, 9)]);
+        // A swap of adjacent characters is one edit.
+        assert_eq!(spans("recieve", 1, "receive"), vec![(0, 7)]);
+        assert!(spans("recieve", 1, "rcveiee").is_empty());
+    }
+
+    #[test]
+    fn matches_end_where_the_distance_is_lowest() {
+        assert_eq!(spans("recieve", 1, "it receives"), vec![(3, 10)]);
+        assert_eq!(spans("foo", 0, "foo fox foo"), vec![(0, 3), (8, 11)]);
+    }
+
+    #[test]
+    fn spans_are_byte_offsets() {
+        assert_eq!(spans("naive", 1, "très naïve"), vec![(6, 12)]);
+        assert!(Fuzzy::new("FOO", 0, false).unwrap().find_iter("foo").is_empty());
+    }
+
+    #[test]
+    fn rejects_terms_no_longer_than_the_distance() {
+        assert!(Fuzzy::new("ab", 2, true).is_err());
+        assert!(Fuzzy::new("abc", 2, true).is_ok());
+    }
+}
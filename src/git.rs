@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lines added or modified by a git diff, by file, in the new version.
+#[derive(Debug, Default)]
+pub struct ChangedLines {
+    files: HashMap<PathBuf, Vec<Range<usize>>>,
+}
+
+impl ChangedLines {
+    /// Reads `git diff <range>` for the repository containing `directory`.
+    /// `range` is anything `git diff` accepts, e.g. `main..HEAD` or a single
+    /// commit to compare the working tree against.
+    pub fn from_git(directory: &str, range: &str) -> Result<ChangedLines, String> {
+        let root = git(directory, &["rev-parse", "--show-toplevel"])?;
+        let root = PathBuf::from(root.trim_end_matches(['\n', '\r']));
+        let diff = git(
+            directory,
+            &[
+                "-c",
+                "core.quotePath=false",
+                "diff",
+                "--no-color",
+                "--no-ext-diff",
+                // `parse` expects the default prefixes, whatever diff.noprefix
+                // or diff.mnemonicPrefix say.
+                "--src-prefix=a/",
+                "--dst-prefix=b/",
+                "--unified=0",
+                range,
+                "--",
+            ],
+        )?;
+        Ok(ChangedLines::parse(&diff, &root))
+    }
+
+    fn parse(diff: &str, root: &Path) -> ChangedLines {
+        let mut files: HashMap<PathBuf, Vec<Range<usize>>> = HashMap::new();
+        let mut current: Option<PathBuf> = None;
+
+        for line in diff.lines() {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                // Deleted files show `+++ /dev/null` and have no new lines.
+                let path = unquote(path).unwrap_or_else(|| path.to_string());
+                current = path.strip_prefix("b/").map(|path| root.join(path));
+            } else if let (Some(hunk), Some(path)) = (line.strip_prefix("@@ "), &current) {
+                if let Some(lines) = new_side(hunk) {
+                    files.entry(path.clone()).or_default().push(lines);
+                }
+            }
+        }
+
+        // Compare canonical paths, since results use whatever spelling the
+        // walk produced.
+        let files = files
+            .into_iter()
+            .map(|(path, lines)| (fs::canonicalize(&path).unwrap_or(path), lines))
+            .collect();
+        ChangedLines { files }
+    }
+
+    /// The changed line ranges (one-based, end-exclusive) of `path`, or
+    /// `None` if the diff didn't touch it.
+    pub fn lines(&self, path: &str) -> Option<&[Range<usize>]> {
+        let path = fs::canonicalize(path).ok()?;
+        self.files.get(&path).map(Vec::as_slice)
+    }
+}
+
+/// The author of each line (1-based) of the file at `path`, from
+/// `git blame`. Lines not committed yet are attributed to "Not Committed
+/// Yet", as git does.
+pub fn blame_authors(path: &str) -> Result<HashMap<usize, String>, String> {
+    let path = Path::new(path);
+    let directory = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let blame = git(&directory.to_string_lossy(), &["blame", "--line-porcelain", "--", &name])?;
+    let mut authors = HashMap::new();
+    let mut line = 0;
+    for entry in blame.lines() {
+        if entry.starts_with('\t') {
+            continue;
+        }
+        let mut words = entry.split_whitespace();
+        match words.next() {
+            Some(sha) if sha.len() == 40 && sha.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                line = words.nth(1).and_then(|n| n.parse().ok()).unwrap_or(0);
+            }
+            Some("author") => {
+                authors.insert(line, entry["author ".len()..].to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(authors)
+}
+
+/// Runs git in `directory`, returning its stdout or, on failure, its stderr.
+fn git(directory: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(args)
+        .output()
+        .map_err(|e| format!("running git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {}: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses the `+start,count` side of a hunk header such as
+/// `-10,2 +12,3 @@ fn main()`. Pure deletions have no new lines.
+fn new_side(hunk: &str) -> Option<Range<usize>> {
+    let new = hunk.split_whitespace().find_map(|part| part.strip_prefix('+'))?;
+    let (start, count) = match new.split_once(',') {
+        Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+        None => (new.parse().ok()?, 1),
+    };
+    (count > 0).then_some(start..start + count)
+}
+
+/// Undoes git's C-style quoting of unusual paths (`"a\tb"`).
+fn unquote(path: &str) -> Option<String> {
+    let inner = path.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_prefixes_ignore_the_user_config() {
+        let dir = std::env::temp_dir().join(format!("fsearch-test-{}-git", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let directory = dir.to_str().unwrap();
+        let run = |args: &[&str]| git(directory, args).unwrap();
+        run(&["init", "-q"]);
+        run(&["config", "diff.noprefix", "true"]);
+        run(&["config", "diff.mnemonicPrefix", "true"]);
+        fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+        run(&["add", "a.txt"]);
+        run(&["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-q", "-m", "a"]);
+        fs::write(dir.join("a.txt"), "one\nTWO\nthree\n").unwrap();
+
+        let changed = ChangedLines::from_git(directory, "HEAD").unwrap();
+        let lines = changed.lines(dir.join("a.txt").to_str().unwrap()).map(|lines| lines.iter().map(|r| (r.start, r.end)).collect());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(lines, Some(vec![(2, 4)]));
+    }
+}
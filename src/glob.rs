@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use regex::Regex;
+
+/// A shell-style pattern matched against paths relative to the search
+/// directory, using `/` as the separator on every platform.
+///
+/// `*` and `?` match within one path component, `**` matches across
+/// components, `[abc]`/`[!abc]` match character classes and `{a,b}` matches
+/// alternatives. A pattern without a `/` matches the file or directory name
+/// at any depth; one with a `/` is anchored at the search directory.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+    /// Also matches directories whose contents the pattern covers entirely,
+    /// e.g. `build` for `build/**`, so the walk can skip them.
+    dir_regex: Option<Regex>,
+    name_only: bool,
+}
+
+/// A `--glob` filter: a glob files must match, or with a leading `!` one
+/// they must not.
+#[derive(Debug, Clone)]
+pub struct GlobFilter {
+    pub glob: Glob,
+    pub negated: bool,
+}
+
+pub fn parse_glob_filter(spec: &str) -> Result<GlobFilter, String> {
+    match spec.strip_prefix('!') {
+        Some(pattern) => Ok(GlobFilter { glob: parse_glob(pattern)?, negated: true }),
+        None => Ok(GlobFilter { glob: parse_glob(spec)?, negated: false }),
+    }
+}
+
+pub fn parse_glob(pattern: &str) -> Result<Glob, String> {
+    let trimmed = pattern.strip_prefix('/').unwrap_or(pattern);
+    let name_only = !pattern.contains('/');
+    let compile = |glob: &str| {
+        Regex::new(&format!("^{}$", to_regex(glob)?))
+            .map_err(|e| format!("invalid glob '{}': {}", pattern, e))
+    };
+    let dir_regex = match trimmed.strip_suffix("/**") {
+        Some(dir) if !dir.is_empty() => Some(compile(dir)?),
+        _ => None,
+    };
+    Ok(Glob { regex: compile(trimmed)?, dir_regex, name_only })
+}
+
+impl Glob {
+    /// Whether the file at `relative` matches.
+    pub fn matches_file(&self, relative: &Path) -> bool {
+        self.regex.is_match(&self.subject(relative))
+    }
+
+    /// Whether the directory at `relative` matches, or everything under it does.
+    pub fn matches_dir(&self, relative: &Path) -> bool {
+        let subject = self.subject(relative);
+        self.regex.is_match(&subject) || self.dir_regex.as_ref().is_some_and(|r| r.is_match(&subject))
+    }
+
+    fn subject(&self, relative: &Path) -> String {
+        if self.name_only {
+            relative.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+        } else {
+            let path = relative.to_string_lossy();
+            let path = path.strip_prefix("./").unwrap_or(&path);
+            path.replace('\\', "/")
+        }
+    }
+}
+
+fn to_regex(glob: &str) -> Result<String, String> {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    let mut alternation = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                regex.push('[');
+                if matches!(chars.peek(), Some('!') | Some('^')) {
+                    chars.next();
+                    regex.push('^');
+                }
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => regex.push_str("\\\\"),
+                        Some(c) => regex.push(c),
+                        None => return Err(format!("unclosed '[' in glob '{}'", glob)),
+                    }
+                }
+                regex.push(']');
+            }
+            '{' => {
+                alternation += 1;
+                regex.push_str("(?:");
+            }
+            ',' if alternation > 0 => regex.push('|'),
+            '}' if alternation > 0 => {
+                alternation -= 1;
+                regex.push(')');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    if alternation > 0 {
+        return Err(format!("unclosed '{{' in glob '{}'", glob));
+    }
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        parse_glob(pattern).unwrap().matches_file(Path::new(path))
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/lib.rs"));
+        assert!(!matches("src/**/*.rs", "tests/a.rs"));
+        assert!(matches("**/fixtures/*", "a/b/fixtures/x.json"));
+        assert!(parse_glob("build/**").unwrap().matches_dir(Path::new("build")));
+    }
+
+    #[test]
+    fn single_star_stays_in_one_component() {
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/a/lib.rs"));
+        assert!(matches("*.rs", "deep/down/lib.rs"));
+        assert!(matches("src/?.rs", "./src/a.rs"));
+    }
+
+    #[test]
+    fn character_classes_and_alternatives() {
+        assert!(matches("file[0-9].txt", "file7.txt"));
+        assert!(!matches("file[0-9].txt", "filex.txt"));
+        assert!(matches("file[!0-9].txt", "filex.txt"));
+        assert!(matches("file[^0-9].txt", "filex.txt"));
+        assert!(matches("*.{js,ts}", "app.ts"));
+        assert!(!matches("*.{js,ts}", "app.rs"));
+        assert!(matches("a+b(1).txt", "a+b(1).txt"));
+    }
+
+    #[test]
+    fn unclosed_brackets_are_errors() {
+        assert!(parse_glob("file[0-9.txt").is_err());
+        assert!(parse_glob("*.{js,ts").is_err());
+    }
+}
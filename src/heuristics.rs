@@ -0,0 +1,100 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes read from the start of a file to estimate its line lengths.
+const SAMPLE_LEN: u64 = 64 * 1024;
+/// Bytes read from the end of a file when looking for a source map footer.
+const FOOTER_LEN: u64 = 512;
+/// Average line length above which a script or stylesheet counts as minified.
+const MINIFIED_LINE_LEN: usize = 250;
+/// Bytes at the start of a file searched for a generated-code marker.
+const HEADER_LEN: u64 = 1024;
+/// Lines at the start of a file searched for a generated-code marker.
+const HEADER_LINES: usize = 5;
+
+/// Dependency lockfiles, which are written by tools rather than people.
+const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "Gemfile.lock",
+    "composer.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "go.sum",
+    "flake.lock",
+];
+
+/// Whether the name alone marks `path` as generated: protobuf output,
+/// lockfiles and source maps.
+pub fn is_generated_name(path: &str) -> bool {
+    let name = Path::new(path).file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    LOCKFILES.contains(&name.as_ref())
+        || name.ends_with(".pb.go")
+        || name.ends_with("_pb2.py")
+        || name.ends_with(".pb.h")
+        || name.ends_with(".pb.cc")
+        || name.ends_with(".map")
+}
+
+/// Whether the first lines of `file` carry an `@generated` marker or Go's
+/// `Code generated ... DO NOT EDIT.` header. Leaves the file positioned at
+/// its start.
+pub fn has_generated_marker(file: &mut fs::File) -> io::Result<bool> {
+    let mut head = Vec::new();
+    file.by_ref().take(HEADER_LEN).read_to_end(&mut head)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let text = String::from_utf8_lossy(&head);
+    Ok(text.lines().take(HEADER_LINES).any(|line| {
+        line.contains("@generated") || (line.contains("Code generated") && line.contains("DO NOT EDIT"))
+    }))
+}
+
+/// Guesses whether `file` is a minified script or stylesheet: a `.min.` file
+/// name, very long lines on average, or a `sourceMappingURL` footer. Leaves
+/// the file positioned at its start.
+pub fn is_minified(path: &str, file: &mut fs::File, len: u64) -> io::Result<bool> {
+    let name = Path::new(path).file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let ext = Path::new(path).extension().map(|e| e.to_string_lossy().to_ascii_lowercase());
+    if !matches!(ext.as_deref(), Some("js" | "mjs" | "cjs" | "css")) {
+        return Ok(false);
+    }
+    if name.contains(".min.") {
+        return Ok(true);
+    }
+
+    let mut head = Vec::new();
+    file.by_ref().take(SAMPLE_LEN).read_to_end(&mut head)?;
+    let lines = head.iter().filter(|&&b| b == b'\n').count() + 1;
+    let mut minified = head.len() / lines > MINIFIED_LINE_LEN;
+
+    if !minified {
+        let tail = if len > SAMPLE_LEN {
+            let mut tail = Vec::new();
+            file.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+            file.by_ref().take(FOOTER_LEN).read_to_end(&mut tail)?;
+            tail
+        } else {
+            head
+        };
+        minified = has_source_map_footer(&tail);
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    Ok(minified)
+}
+
+/// Whether the last non-empty line is a `//# sourceMappingURL=` (or CSS
+/// `/*# sourceMappingURL=`) comment.
+fn has_source_map_footer(tail: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(tail);
+    let last = text.trim_end().rsplit('\n').next().unwrap_or("").trim_start();
+    ["//# sourceMappingURL=", "//@ sourceMappingURL=", "/*# sourceMappingURL="]
+        .iter()
+        .any(|prefix| last.starts_with(prefix))
+}
@@ -0,0 +1,285 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use colored::Colorize;
+
+use crate::text;
+use crate::theme::{self, Themed};
+
+/// Lexical rules for one language family. This is a lightweight tokenizer,
+/// not a parser: it recognizes keywords, comments, string literals and
+/// numbers, which is enough to make matched code readable at a glance.
+pub struct Syntax {
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+        "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+        "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+};
+
+const C_LIKE: Syntax = Syntax {
+    keywords: &[
+        "auto", "break", "case", "catch", "char", "class", "const", "continue", "default", "delete", "do",
+        "double", "else", "enum", "extends", "extern", "false", "final", "float", "for", "func", "go", "goto",
+        "if", "implements", "import", "int", "interface", "long", "namespace", "new", "null", "nullptr",
+        "package", "private", "protected", "public", "return", "short", "signed", "sizeof", "static", "struct",
+        "switch", "template", "this", "throw", "true", "try", "typedef", "union", "unsigned", "using", "var",
+        "virtual", "void", "volatile", "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    keywords: &[
+        "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "else",
+        "export", "extends", "false", "finally", "for", "from", "function", "if", "import", "in", "instanceof",
+        "interface", "let", "new", "null", "return", "static", "super", "switch", "this", "throw", "true", "try",
+        "type", "typeof", "undefined", "var", "void", "while", "yield",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+};
+
+const PYTHON: Syntax = Syntax {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else",
+        "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "None",
+        "nonlocal", "not", "or", "pass", "raise", "return", "True", "try", "while", "with", "yield",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+};
+
+const SHELL: Syntax = Syntax {
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+        "return", "then", "until", "while",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+};
+
+const CONFIG: Syntax = Syntax {
+    keywords: &["true", "false", "null", "yes", "no", "on", "off"],
+    line_comments: &["#", ";"],
+    block_comment: None,
+    quotes: &['"', '\''],
+};
+
+/// Picks rules from the file extension, or `None` for unknown files.
+pub fn detect(path: &str) -> Option<&'static Syntax> {
+    let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "rs" => &RUST,
+        "c" | "h" | "cc" | "cpp" | "hpp" | "cxx" | "java" | "go" | "cs" | "kt" | "swift" | "scala" => &C_LIKE,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &JAVASCRIPT,
+        "py" | "pyi" | "rb" => &PYTHON,
+        "sh" | "bash" | "zsh" => &SHELL,
+        "toml" | "ini" | "cfg" | "conf" | "yaml" | "yml" | "properties" => &CONFIG,
+        _ => return None,
+    })
+}
+
+/// The part of source code `--scope` restricts matching to.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Scope {
+    /// Line and block comments
+    Comments,
+    /// String literals, quotes included
+    Strings,
+    /// Everything outside comments and strings
+    Code,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Plain,
+    Keyword,
+    Comment,
+    Str,
+    Number,
+}
+
+/// Carries multi-line state (an open block comment) between lines.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct State {
+    in_block_comment: bool,
+}
+
+impl Syntax {
+    /// Splits `line` into byte ranges tagged with their token kind.
+    fn tokenize(&self, line: &str, state: &mut State) -> Vec<(usize, usize, Token)> {
+        let mut tokens = Vec::new();
+        let bytes = line.as_bytes();
+        let mut i = 0;
+
+        while i < line.len() {
+            let rest = &line[i..];
+
+            if state.in_block_comment {
+                let (_, close) = self.block_comment.unwrap_or(("", ""));
+                let end = rest.find(close).map_or(line.len(), |p| {
+                    state.in_block_comment = false;
+                    i + p + close.len()
+                });
+                tokens.push((i, end, Token::Comment));
+                i = end;
+                continue;
+            }
+
+            if self.line_comments.iter().any(|c| rest.starts_with(c)) {
+                tokens.push((i, line.len(), Token::Comment));
+                break;
+            }
+            if let Some((open, _)) = self.block_comment {
+                if rest.starts_with(open) {
+                    state.in_block_comment = true;
+                    tokens.push((i, i + open.len(), Token::Comment));
+                    i += open.len();
+                    continue;
+                }
+            }
+
+            let c = rest.chars().next().unwrap_or(' ');
+            if self.quotes.contains(&c) {
+                let body = i + c.len_utf8();
+                let mut end = line.len();
+                let mut escaped = false;
+                for (offset, ch) in line[body..].char_indices() {
+                    if escaped {
+                        escaped = false;
+                    } else if ch == '\\' {
+                        escaped = true;
+                    } else if ch == c {
+                        end = body + offset + ch.len_utf8();
+                        break;
+                    }
+                }
+                tokens.push((i, end, Token::Str));
+                i = end;
+                continue;
+            }
+
+            if c.is_alphanumeric() || c == '_' {
+                let end = rest
+                    .char_indices()
+                    .find(|&(_, ch)| !(ch.is_alphanumeric() || ch == '_'))
+                    .map_or(line.len(), |(p, _)| i + p);
+                let word = &line[i..end];
+                let token = if bytes[i].is_ascii_digit() {
+                    Token::Number
+                } else if self.keywords.contains(&word) {
+                    Token::Keyword
+                } else {
+                    Token::Plain
+                };
+                tokens.push((i, end, token));
+                i = end;
+                continue;
+            }
+
+            tokens.push((i, i + c.len_utf8(), Token::Plain));
+            i += c.len_utf8();
+        }
+
+        tokens
+    }
+}
+
+impl Syntax {
+    /// The byte ranges of `line` that lie in `scope`, with adjacent tokens
+    /// merged so a match may span them. Call this on every line of a file in
+    /// order, so block comments are tracked across lines.
+    pub fn scope_ranges(&self, line: &str, state: &mut State, scope: Scope) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (start, end, token) in self.tokenize(line, state) {
+            let inside = match scope {
+                Scope::Comments => token == Token::Comment,
+                Scope::Strings => token == Token::Str,
+                Scope::Code => !matches!(token, Token::Comment | Token::Str),
+            };
+            if !inside {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+        ranges
+    }
+}
+
+/// Drops the matches, and their terms, that are not wholly inside one of
+/// `ranges`.
+pub fn retain_in_scope(ranges: &[(usize, usize)], matches: &mut Vec<(usize, usize)>, terms: &mut Vec<usize>) {
+    let inside = |&(s, e): &(usize, usize)| ranges.iter().any(|&(start, end)| start <= s && e <= end);
+    if matches.iter().all(inside) {
+        return;
+    }
+    let (kept, kept_terms): (Vec<_>, Vec<_>) = matches
+        .iter()
+        .enumerate()
+        .filter(|(_, span)| inside(span))
+        .map(|(i, &span)| (span, terms.get(i).copied()))
+        .unzip();
+    *matches = kept;
+    *terms = kept_terms.into_iter().flatten().collect();
+}
+
+fn paint(text: &str, token: Token, term: Option<usize>) -> String {
+    let palette = theme::palette();
+    let styled = match token {
+        Token::Plain => text.normal(),
+        Token::Keyword => text.themed(palette.keyword),
+        Token::Comment => text.themed(palette.comment),
+        Token::Str => text.themed(palette.string),
+        Token::Number => text.themed(palette.number),
+    };
+    match term {
+        Some(term) => palette.paint_term(styled, term).to_string(),
+        None => styled.to_string(),
+    }
+}
+
+/// Renders `line` with syntax colors, layering match highlights on top.
+/// `matches` are byte ranges into `line`, and `terms` the term each came
+/// from (the first term where it is short).
+pub fn highlight(line: &str, syntax: &Syntax, state: &mut State, matches: &[(usize, usize)], terms: &[usize]) -> String {
+    let mut out = String::new();
+    let matches: Vec<_> = matches.iter().map(|&(s, e)| text::snap_span(line, s, e)).collect();
+
+    for (start, end, token) in syntax.tokenize(line, state) {
+        // Split each token at match boundaries so a match inside a token
+        // keeps the token's color underneath the highlight.
+        let mut pos = start;
+        while pos < end {
+            let term = matches.iter().position(|&(s, e)| s <= pos && pos < e).map(|i| terms.get(i).copied().unwrap_or(0));
+            let next = matches
+                .iter()
+                .flat_map(|&(s, e)| [s, e])
+                .filter(|&b| b > pos && b < end)
+                .min()
+                .unwrap_or(end);
+            out.push_str(&paint(&line[pos..next], token, term));
+            pos = next;
+        }
+    }
+
+    out
+}
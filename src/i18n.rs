@@ -0,0 +1,128 @@
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// A message catalog: message id to text, with `{name}` placeholders.
+/// Translations only need to list the messages they cover; anything missing
+/// falls back to English.
+type Catalog = &'static [(&'static str, &'static str)];
+
+const EN: Catalog = &[
+    ("no-matches", "No matches found."),
+    ("matches-found", "{count} matches found"),
+    ("sampled-matches", "{shown} of {count} matches shown (a random sample)"),
+    ("file-summary", "{matches} matches across {lines} lines"),
+    ("total-summary", "{matches} matches across {lines} lines in {files} files"),
+    ("dir-summary", "{matches} matches in {files} files"),
+    ("count-total", "{lines} matching lines in {files} files"),
+    ("binary-match", "binary match at byte {offset}"),
+    ("replace-file", "{lines} lines changed ({occurrences} replacements)"),
+    ("replace-summary", "{occurrences} replacements on {lines} lines in {files} files"),
+    ("distinct-values", "{count} distinct values found"),
+    ("non-numeric-skipped", "{count} non-numeric values skipped, e.g.:"),
+    ("error", "Error: {detail}"),
+    ("invalid-template", "invalid template: {detail}"),
+    ("invalid-config", "invalid configuration: {detail}"),
+    ("invalid-pattern", "invalid pattern: {detail}"),
+    ("walk-skipped", "warning: skipped unreadable directory {path}: {detail}"),
+    ("unknown-flag", "unknown option '{flag}' (see fsearch --help)"),
+    ("interactive-needs-tty", "--interactive requires stdout to be a terminal"),
+    ("pager-prompt", "-- more ({count} remaining): space next page, enter next line, q quit --"),
+];
+
+const DE: Catalog = &[
+    ("no-matches", "Keine Treffer gefunden."),
+    ("matches-found", "{count} Treffer gefunden"),
+    ("sampled-matches", "{shown} von {count} Treffern angezeigt (zufällige Stichprobe)"),
+    ("file-summary", "{matches} Treffer in {lines} Zeilen"),
+    ("total-summary", "{matches} Treffer in {lines} Zeilen in {files} Dateien"),
+    ("dir-summary", "{matches} Treffer in {files} Dateien"),
+    ("count-total", "{lines} passende Zeilen in {files} Dateien"),
+    ("binary-match", "Binärtreffer bei Byte {offset}"),
+    ("replace-file", "{lines} Zeilen geändert ({occurrences} Ersetzungen)"),
+    ("replace-summary", "{occurrences} Ersetzungen in {lines} Zeilen in {files} Dateien"),
+    ("distinct-values", "{count} verschiedene Werte gefunden"),
+    ("non-numeric-skipped", "{count} nicht-numerische Werte übersprungen, z. B.:"),
+    ("error", "Fehler: {detail}"),
+    ("invalid-template", "ungültige Vorlage: {detail}"),
+    ("invalid-config", "ungültige Konfiguration: {detail}"),
+    ("invalid-pattern", "ungültiges Suchmuster: {detail}"),
+    ("walk-skipped", "Warnung: nicht lesbares Verzeichnis {path} übersprungen: {detail}"),
+    ("unknown-flag", "unbekannte Option '{flag}' (siehe fsearch --help)"),
+    ("interactive-needs-tty", "--interactive erfordert ein Terminal als Standardausgabe"),
+    ("pager-prompt", "-- mehr ({count} übrig): Leertaste nächste Seite, Enter nächste Zeile, q beenden --"),
+];
+
+const ES: Catalog = &[
+    ("no-matches", "No se encontraron coincidencias."),
+    ("matches-found", "{count} coincidencias encontradas"),
+    ("sampled-matches", "{shown} de {count} coincidencias mostradas (una muestra aleatoria)"),
+    ("file-summary", "{matches} coincidencias en {lines} líneas"),
+    ("total-summary", "{matches} coincidencias en {lines} líneas de {files} archivos"),
+    ("dir-summary", "{matches} coincidencias en {files} archivos"),
+    ("count-total", "{lines} líneas coincidentes en {files} archivos"),
+    ("binary-match", "coincidencia binaria en el byte {offset}"),
+    ("replace-file", "{lines} líneas modificadas ({occurrences} reemplazos)"),
+    ("replace-summary", "{occurrences} reemplazos en {lines} líneas de {files} archivos"),
+    ("distinct-values", "{count} valores distintos encontrados"),
+    ("non-numeric-skipped", "{count} valores no numéricos omitidos, p. ej.:"),
+    ("error", "Error: {detail}"),
+    ("invalid-template", "plantilla no válida: {detail}"),
+    ("invalid-config", "configuración no válida: {detail}"),
+    ("invalid-pattern", "patrón no válido: {detail}"),
+    ("walk-skipped", "aviso: se omitió el directorio ilegible {path}: {detail}"),
+    ("unknown-flag", "opción desconocida '{flag}' (ver fsearch --help)"),
+    ("interactive-needs-tty", "--interactive requiere que la salida estándar sea una terminal"),
+    ("pager-prompt", "-- más ({count} restantes): espacio página siguiente, intro línea siguiente, q salir --"),
+];
+
+/// Registered translations by language code. To contribute one, add a
+/// catalog above and list it here.
+const CATALOGS: &[(&str, Catalog)] = &[("de", DE), ("es", ES)];
+
+fn catalog() -> Option<Catalog> {
+    static ACTIVE: OnceLock<Option<Catalog>> = OnceLock::new();
+    *ACTIVE.get_or_init(|| {
+        let language = detect_language()?;
+        CATALOGS.iter().find(|(code, _)| *code == language).map(|(_, c)| *c)
+    })
+}
+
+/// Reads the language from the POSIX locale variables in priority order,
+/// e.g. `de_DE.UTF-8` yields `de`.
+fn detect_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .split(['_', '.', '@', '-'])
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+        })
+}
+
+fn lookup(catalog: Catalog, id: &str) -> Option<&'static str> {
+    catalog.iter().find(|(key, _)| *key == id).map(|(_, text)| *text)
+}
+
+/// Returns the localized text for `id` with `{name}` placeholders filled in.
+pub fn tr(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let text = catalog()
+        .and_then(|c| lookup(c, id))
+        .or_else(|| lookup(EN, id))
+        .unwrap_or(id);
+
+    let mut out = text.to_string();
+    for (name, value) in args {
+        out = out.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    out
+}
+
+/// Formats the standard `Error: ...` line.
+pub fn error(detail: impl Display) -> String {
+    tr("error", &[("detail", &detail)])
+}
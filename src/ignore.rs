@@ -0,0 +1,207 @@
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use crate::glob::{parse_glob, Glob};
+
+/// One line of a gitignore file.
+struct Rule {
+    glob: Glob,
+    /// `!pattern`: re-include what an earlier rule excluded.
+    negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+}
+
+/// Where the directory of a gitignore file sits relative to the search
+/// directory, since its patterns are relative to that directory.
+enum Base {
+    /// At this path under the search directory.
+    Below(PathBuf),
+    /// Above the search directory, which is at this path under it.
+    Above(PathBuf),
+}
+
+struct Level {
+    rules: Vec<Rule>,
+    base: Base,
+    parent: Option<Arc<Level>>,
+}
+
+/// The gitignore rules in effect in one directory of a walk: its own
+/// `.gitignore` on top of those of the directories above it, the
+/// repository's `.git/info/exclude` and git's global excludes file. As in
+/// git, the rules closest to a path take precedence, and within one file
+/// the last matching rule wins.
+#[derive(Clone, Default)]
+pub struct Ignores(Option<Arc<Level>>);
+
+impl Ignores {
+    /// The rules in effect at the search directory `root`, not counting its
+    /// own `.gitignore`.
+    pub fn for_root(root: &Path) -> Ignores {
+        let absolute = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let top = absolute.ancestors().find(|dir| dir.join(".git").exists());
+        let above = |dir: &Path| Base::Above(absolute.strip_prefix(dir).map(Path::to_path_buf).unwrap_or_default());
+
+        let mut ignores = Ignores::default();
+        if let Some(path) = global_excludes() {
+            ignores = ignores.with(&path, above(top.unwrap_or(&absolute)));
+        }
+        if let Some(top) = top {
+            ignores = ignores.with(&top.join(".git").join("info").join("exclude"), above(top));
+            let mut dirs: Vec<&Path> = absolute.ancestors().skip(1).take_while(|dir| dir.starts_with(top)).collect();
+            dirs.reverse();
+            for dir in dirs {
+                ignores = ignores.with(&dir.join(".gitignore"), above(dir));
+            }
+        }
+        ignores
+    }
+
+    /// The rules in effect in `dir`, at `relative` under the search
+    /// directory: these plus its `.gitignore`, if it has one.
+    pub fn enter(&self, dir: &Path, relative: &Path) -> Ignores {
+        self.with(&dir.join(".gitignore"), Base::Below(relative.to_path_buf()))
+    }
+
+    fn with(&self, file: &Path, base: Base) -> Ignores {
+        let Ok(text) = fs::read_to_string(file) else { return self.clone() };
+        let rules: Vec<Rule> = text.lines().filter_map(parse_rule).collect();
+        if rules.is_empty() {
+            return self.clone();
+        }
+        Ignores(Some(Arc::new(Level { rules, base, parent: self.0.clone() })))
+    }
+
+    /// Whether the file or directory at `relative` under the search
+    /// directory is ignored.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let mut level = self.0.as_deref();
+        while let Some(current) = level {
+            let subject = match &current.base {
+                Base::Below(base) => Cow::Borrowed(relative.strip_prefix(base).unwrap_or(relative)),
+                Base::Above(prefix) => Cow::Owned(prefix.join(relative)),
+            };
+            let matched = current.rules.iter().rev().find(|rule| match is_dir {
+                true => rule.glob.matches_dir(&subject),
+                false => !rule.dir_only && rule.glob.matches_file(&subject),
+            });
+            if let Some(rule) = matched {
+                return !rule.negated;
+            }
+            level = current.parent.as_deref();
+        }
+        false
+    }
+}
+
+/// `core.excludesFile`, or git's default of `$XDG_CONFIG_HOME/git/ignore`.
+fn global_excludes() -> Option<PathBuf> {
+    let configured = Command::new("git")
+        .args(["config", "--global", "--path", "--get", "core.excludesFile"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+        .filter(|path| !path.as_os_str().is_empty());
+    configured.or_else(|| {
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("git").join("ignore"))
+    })
+}
+
+/// Parses a gitignore line; blank lines, comments and patterns that don't
+/// compile are skipped.
+fn parse_rule(line: &str) -> Option<Rule> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    // Trailing spaces don't count unless the last one is escaped.
+    let trimmed = line.trim_end_matches(' ');
+    let line: Cow<str> = match trimmed.strip_suffix('\\') {
+        Some(escaped) if trimmed.len() < line.len() => Cow::Owned(format!("{} ", escaped)),
+        _ => Cow::Borrowed(trimmed),
+    };
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, &*line),
+    };
+    let pattern = pattern.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(pattern);
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(dir) => (true, dir),
+        None => (false, pattern),
+    };
+    if pattern.is_empty() {
+        return None;
+    }
+    parse_glob(pattern).ok().map(|glob| Rule { glob, negated, dir_only })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `parent` with one more gitignore file, whose `text` sits at `base`.
+    fn layer(parent: &Ignores, text: &str, base: Base) -> Ignores {
+        let rules = text.lines().filter_map(parse_rule).collect();
+        Ignores(Some(Arc::new(Level { rules, base, parent: parent.0.clone() })))
+    }
+
+    fn root(text: &str) -> Ignores {
+        layer(&Ignores::default(), text, Base::Below(PathBuf::new()))
+    }
+
+    fn ignored(ignores: &Ignores, path: &str) -> bool {
+        ignores.is_ignored(Path::new(path), false)
+    }
+
+    #[test]
+    fn negation_reincludes_and_last_rule_wins() {
+        let ignores = root("*.log\n!keep.log\n");
+        assert!(ignored(&ignores, "debug.log"));
+        assert!(ignored(&ignores, "sub/debug.log"));
+        assert!(!ignored(&ignores, "keep.log"));
+        assert!(!ignored(&ignores, "sub/keep.log"));
+        assert!(ignored(&root("!keep.log\n*.log\n"), "keep.log"));
+    }
+
+    #[test]
+    fn slashes_anchor_and_mark_directories() {
+        let ignores = root("/build\ndocs/*.md\ntarget/\n");
+        assert!(ignores.is_ignored(Path::new("build"), true));
+        assert!(!ignores.is_ignored(Path::new("src/build"), true));
+        assert!(ignored(&ignores, "docs/intro.md"));
+        assert!(!ignored(&ignores, "src/docs/intro.md"));
+        assert!(ignores.is_ignored(Path::new("src/target"), true));
+        assert!(!ignored(&ignores, "target"));
+    }
+
+    #[test]
+    fn nearer_files_take_precedence() {
+        let outer = root("*.log\n");
+        let inner = layer(&outer, "!debug.log\n", Base::Below(PathBuf::from("sub")));
+        assert!(!ignored(&inner, "sub/debug.log"));
+        assert!(ignored(&inner, "sub/other.log"));
+        // A file above the search directory, which is 'proj' under it.
+        let above = layer(&Ignores::default(), "/proj/tmp\n", Base::Above(PathBuf::from("proj")));
+        assert!(ignored(&above, "tmp"));
+        assert!(!ignored(&above, "proj/tmp"));
+    }
+
+    #[test]
+    fn comments_blanks_and_escapes() {
+        let ignores = root("# comment\n\n\\#hash\n\\!bang\nspace\\ \ntrailing   \n");
+        assert!(!ignored(&ignores, "# comment"));
+        assert!(ignored(&ignores, "#hash"));
+        assert!(ignored(&ignores, "!bang"));
+        assert!(ignored(&ignores, "space "));
+        assert!(ignored(&ignores, "trailing"));
+    }
+}
@@ -0,0 +1,260 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::pattern::Matcher;
+use crate::{Args, SearchResult};
+
+/// How one language names the modules a file depends on: patterns that
+/// capture the module of an import statement as `module`, as `list` when
+/// the statement takes several comma-separated modules with optional
+/// `as` aliases, or as `tree` for a Rust use tree such as `std::{fs, io}`.
+struct Language {
+    extensions: &'static [&'static str],
+    statements: &'static [&'static str],
+    /// Go's `import ( ... )` blocks, with one quoted path per line.
+    blocks: bool,
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        statements: &[r"^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+(?:::)?(?P<tree>[^;]+)", r"^\s*extern\s+crate\s+(?P<module>\w+)"],
+        blocks: false,
+    },
+    Language {
+        extensions: &["py", "pyi"],
+        statements: &[r"^\s*from\s+(?P<module>\.*[\w.]*)\s+import\b", r"^\s*import\s+(?P<list>[\w.]+(?:\s+as\s+\w+)?(?:\s*,\s*[\w.]+(?:\s+as\s+\w+)?)*)"],
+        blocks: false,
+    },
+    Language {
+        extensions: &["js", "mjs", "cjs", "jsx", "ts", "tsx", "mts", "cts", "vue", "svelte"],
+        statements: &[
+            r#"^\s*import\s+['"](?P<module>[^'"]+)['"]"#,
+            r#"\bfrom\s+['"](?P<module>[^'"]+)['"]"#,
+            r#"\b(?:require|import)\s*\(\s*['"](?P<module>[^'"]+)['"]\s*\)"#,
+        ],
+        blocks: false,
+    },
+    Language {
+        extensions: &["go"],
+        statements: &[r#"^\s*import\s+(?:[\w.]+\s+)?"(?P<module>[^"]+)""#],
+        blocks: true,
+    },
+    Language {
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hh", "hpp", "hxx", "m", "mm"],
+        statements: &[r#"^\s*#\s*(?:include|import)\s*[<"](?P<module>[^>"]+)[>"]"#],
+        blocks: false,
+    },
+    Language {
+        extensions: &["java", "kt", "kts", "scala"],
+        statements: &[r"^\s*import\s+(?:static\s+)?(?P<module>[\w.]+)"],
+        blocks: false,
+    },
+    Language {
+        extensions: &["cs"],
+        statements: &[r"^\s*(?:global\s+)?using\s+(?:static\s+)?(?:\w+\s*=\s*)?(?P<module>[\w.]+)\s*;"],
+        blocks: false,
+    },
+    Language {
+        extensions: &["rb"],
+        statements: &[r#"^\s*(?:require|require_relative|load)\s*\(?\s*['"](?P<module>[^'"]+)['"]"#],
+        blocks: false,
+    },
+    Language {
+        extensions: &["php"],
+        statements: &[r"^\s*use\s+(?:function\s+|const\s+)?\\?(?P<module>[\w\\]+)", r#"\b(?:require|include)(?:_once)?\s*\(?\s*['"](?P<module>[^'"]+)['"]"#],
+        blocks: false,
+    },
+];
+
+/// Compiled `statements` of each language, plus the pattern for the lines
+/// of a Go import block.
+struct Compiled {
+    statements: Vec<Vec<Regex>>,
+    block_line: Regex,
+}
+
+fn compiled() -> &'static Compiled {
+    static COMPILED: OnceLock<Compiled> = OnceLock::new();
+    COMPILED.get_or_init(|| Compiled {
+        statements: LANGUAGES
+            .iter()
+            .map(|language| language.statements.iter().map(|pattern| Regex::new(pattern).unwrap()).collect())
+            .collect(),
+        block_line: Regex::new(r#"^\s*(?:[\w.]+\s+)?"(?P<module>[^"]+)""#).unwrap(),
+    })
+}
+
+/// The index into `LANGUAGES` of the language of `path`, by its extension.
+pub fn language(path: &str) -> Option<usize> {
+    let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    LANGUAGES.iter().position(|language| language.extensions.contains(&ext.as_str()))
+}
+
+/// Whether the imported `spec` is `module` or something inside it, e.g.
+/// `react/jsx-runtime` or `std::fs` but not `react-dom`. With --regex the
+/// pattern only has to match somewhere in `spec` instead.
+fn imports(spec: &str, module: &str, pattern: &Matcher, args: &Args) -> bool {
+    if args.regex {
+        return !pattern.find(spec).0.is_empty();
+    }
+    let module = module.trim_end_matches(['/', '.', ':']);
+    match spec.strip_prefix(module) {
+        Some(rest) => rest.is_empty() || rest.starts_with(['/', '.', '\\']) || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// Finds the import statements in `content` that depend on the searched
+/// module. Each result is one imported module, highlighted in its line.
+pub fn search(content: &str, file_path: &str, language: usize, pattern: &Matcher, args: &Args) -> Vec<SearchResult> {
+    let compiled = compiled();
+    let statements = &compiled.statements[language];
+    let module = args.term.first().map(String::as_str).unwrap_or_default();
+    let mut in_block = false;
+    let mut results = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        // Each imported module, and the span of the line naming it.
+        let mut specs: Vec<(usize, usize, Cow<str>)> = Vec::new();
+        if in_block {
+            if line.trim_start().starts_with(')') {
+                in_block = false;
+            } else if let Some(m) = compiled.block_line.captures(line).and_then(|caps| caps.name("module")) {
+                specs.push((m.start(), m.end(), Cow::Borrowed(m.as_str())));
+            }
+        } else if LANGUAGES[language].blocks && line.trim_start().starts_with("import") && line.trim_end().ends_with('(') {
+            in_block = true;
+        }
+        for regex in statements {
+            for caps in regex.captures_iter(line) {
+                if let Some(m) = caps.name("module") {
+                    specs.push((m.start(), m.end(), Cow::Borrowed(m.as_str())));
+                } else if let Some(list) = caps.name("list") {
+                    let mut offset = list.start();
+                    for item in list.as_str().split(',') {
+                        let name = item.split_whitespace().next().unwrap_or_default();
+                        let start = offset + item.find(name).unwrap_or(0);
+                        specs.push((start, start + name.len(), Cow::Borrowed(name)));
+                        offset += item.len() + 1;
+                    }
+                } else if let Some(tree) = caps.name("tree") {
+                    use_tree(tree.as_str(), tree.start(), "", &mut specs);
+                }
+            }
+        }
+
+        specs.sort_unstable();
+        specs.dedup();
+        let matches: Vec<(usize, usize)> = specs
+            .into_iter()
+            .filter(|(_, _, spec)| !spec.is_empty() && imports(spec, module, pattern, args))
+            .map(|(start, end, _)| (start, end))
+            .collect();
+        if matches.is_empty() {
+            continue;
+        }
+        results.push(SearchResult {
+            file_path: file_path.to_string(),
+            line_number: index + 1,
+            line: line.to_string(),
+            terms: vec![0; matches.len()],
+            matches,
+            byte_offset: None,
+            origin: None,
+            cell: None,
+            context: Vec::new(),
+        });
+        if args.stop_at_first_match() {
+            break;
+        }
+    }
+    results
+}
+
+/// Adds the modules a Rust use tree imports, found at `offset` in its line,
+/// each prefixed by the path of the groups around it: `std::{fs, io::Read}`
+/// imports `std::fs` and `std::io::Read`, `a::{self as b}` imports `a`.
+/// A module spans its last path in the line, such as `io::Read`.
+fn use_tree(tree: &str, offset: usize, prefix: &str, specs: &mut Vec<(usize, usize, Cow<str>)>) {
+    let join = |path: &str| match (prefix, path) {
+        (_, "self" | "*") | (_, "") => prefix.to_string(),
+        ("", path) => path.to_string(),
+        (prefix, path) => format!("{}::{}", prefix, path),
+    };
+    let mut depth = 0;
+    let mut item_start = 0;
+    for (index, c) in tree.char_indices().chain([(tree.len(), ',')]) {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 || index == tree.len() => {
+                let item = &tree[item_start..index];
+                let start = offset + item_start + (item.len() - item.trim_start().len());
+                let item = item.trim();
+                match item.split_once('{') {
+                    Some((path, group)) => {
+                        let path = path.trim();
+                        match group.rsplit_once('}') {
+                            Some((group, _)) => use_tree(group, start + path.len() + 1, &join(path.trim_end_matches("::")), specs),
+                            // The group goes on past this line.
+                            None => specs.push((start, start + path.len(), Cow::Owned(join(path.trim_end_matches("::"))))),
+                        }
+                    }
+                    None => {
+                        let path = item.split_whitespace().next().unwrap_or_default();
+                        let module = join(path.trim_end_matches("::*"));
+                        specs.push((start, start + path.len(), Cow::Owned(module)));
+                    }
+                }
+                item_start = index + 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern;
+    use clap::Parser;
+
+    /// The highlighted text of each line of `content`, a Rust file, that
+    /// imports `module`.
+    fn rust_imports(content: &str, module: &str) -> Vec<Vec<String>> {
+        let args = Args::parse_from(["fsearch", "-t", module]);
+        let pattern = pattern::compile(&args).unwrap();
+        search(content, "a.rs", language("a.rs").unwrap(), &pattern, &args)
+            .iter()
+            .map(|result| result.matches.iter().map(|&(start, end)| result.line[start..end].to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn grouped_use_imports_each_item() {
+        let content = "use std::fs::File;\nuse std::{fs, io};\nuse std::fsx;\n";
+        assert_eq!(rust_imports(content, "std::fs"), [vec!["std::fs::File"], vec!["fs"]]);
+        assert_eq!(rust_imports(content, "std::io"), [vec!["io"]]);
+    }
+
+    #[test]
+    fn nested_groups_keep_their_prefix() {
+        let content = "pub(crate) use std::{collections::{BTreeMap, HashMap}, io::{self, Read}};\n";
+        assert_eq!(rust_imports(content, "std::collections::HashMap"), [vec!["HashMap"]]);
+        assert_eq!(rust_imports(content, "std::io"), [vec!["self", "Read"]]);
+        assert!(rust_imports(content, "std::fs").is_empty());
+        assert_eq!(rust_imports("use std::{\n    fs,\n};\n", "std"), [vec!["std::"]]);
+    }
+
+    #[test]
+    fn aliases_and_globs_name_the_module() {
+        let content = "use std::io::Result as IoResult;\nuse std::{fmt as f};\nuse crate::output::*;\nextern crate serde;\n";
+        assert_eq!(rust_imports(content, "std::io"), [vec!["std::io::Result"]]);
+        assert_eq!(rust_imports(content, "std::fmt"), [vec!["fmt"]]);
+        assert_eq!(rust_imports(content, "crate::output"), [vec!["crate::output::*"]]);
+        assert_eq!(rust_imports(content, "serde"), [vec!["serde"]]);
+    }
+}
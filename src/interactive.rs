@@ -0,0 +1,185 @@
+use std::fs;
+use std::io::{self, BufWriter};
+
+use colored::Colorize;
+use console::{Key, Term};
+
+use crate::highlight::{self, State};
+use crate::output::Printer;
+use crate::template::Template;
+use crate::text;
+use crate::theme::{self, Themed};
+use crate::{Args, SearchResult};
+
+/// Full-screen browser over collected results. Typing narrows the list
+/// instantly (case-insensitive substring over path and line), arrows move
+/// the selection, Tab marks or unmarks the selected result, and Esc quits.
+/// Enter prints the marked results — or every filtered one when nothing is
+/// marked — in the chosen output format, to `--export` if given. On tall
+/// enough terminals the lower half previews the selected match in its file,
+/// syntax highlighted.
+pub fn browse(results: Vec<SearchResult>, args: &Args, template: Option<&Template>) -> io::Result<()> {
+    let term = Term::stdout();
+    let haystacks: Vec<String> = results
+        .iter()
+        .map(|r| format!("{}:{} {}", r.file_path, r.line_number, r.line).to_lowercase())
+        .collect();
+
+    let mut query = String::new();
+    let mut visible: Vec<usize> = (0..results.len()).collect();
+    let mut selected = 0;
+    let mut marked = vec![false; results.len()];
+    let mut preview = Preview::default();
+
+    term.hide_cursor()?;
+    let outcome = loop {
+        render(&term, &results, &visible, &marked, &query, selected, &mut preview)?;
+
+        match term.read_key()? {
+            Key::Escape => break None,
+            Key::Enter if marked.contains(&true) => {
+                break Some((0..results.len()).filter(|&i| marked[i]).collect());
+            }
+            Key::Enter => break Some(visible),
+            Key::Tab => {
+                if let Some(&index) = visible.get(selected) {
+                    marked[index] = !marked[index];
+                    selected = (selected + 1).min(visible.len().saturating_sub(1));
+                }
+            }
+            Key::ArrowUp => selected = selected.saturating_sub(1),
+            Key::ArrowDown => selected = (selected + 1).min(visible.len().saturating_sub(1)),
+            Key::Backspace => {
+                query.pop();
+                visible = filter(&haystacks, &query);
+                selected = 0;
+            }
+            Key::Char(c) if !c.is_control() => {
+                query.push(c);
+                // Appending only narrows, so refilter the current subset.
+                let needle = query.to_lowercase();
+                visible.retain(|&i| haystacks[i].contains(&needle));
+                selected = 0;
+            }
+            _ => {}
+        }
+    };
+    term.show_cursor()?;
+    term.clear_screen()?;
+
+    if let Some(chosen) = outcome {
+        let mut printer = match &args.export {
+            Some(path) => Printer::with_output(args, template, Box::new(BufWriter::new(fs::File::create(path)?))),
+            None => Printer::new(args, template),
+        };
+        for index in chosen {
+            printer.print(std::slice::from_ref(&results[index]));
+        }
+        printer.finish();
+    }
+    Ok(())
+}
+
+fn filter(haystacks: &[String], query: &str) -> Vec<usize> {
+    let needle = query.to_lowercase();
+    (0..haystacks.len()).filter(|&i| haystacks[i].contains(&needle)).collect()
+}
+
+fn render(
+    term: &Term,
+    results: &[SearchResult],
+    visible: &[usize],
+    marked: &[bool],
+    query: &str,
+    selected: usize,
+    preview: &mut Preview,
+) -> io::Result<()> {
+    let (rows, cols) = term.size();
+    // Leave the last row empty so the final newline doesn't scroll the header away.
+    let available = (rows as usize).saturating_sub(3).max(1);
+    let preview_rows = if available >= 12 { available / 2 } else { 0 };
+    let list_rows = available - preview_rows;
+    let first = selected.saturating_sub(list_rows - 1);
+
+    term.clear_screen()?;
+    let marks = marked.iter().filter(|&&m| m).count();
+    let marks = if marks > 0 { format!("  {} marked", marks) } else { String::new() };
+    term.write_line(&format!(
+        "{} {}  {}",
+        ">".themed(theme::palette().count).bold(),
+        query,
+        format!("{}/{}{}", visible.len(), results.len(), marks).dimmed()
+    ))?;
+    term.write_line(&"─".repeat(cols as usize).dimmed().to_string())?;
+
+    for (row, &index) in visible.iter().enumerate().skip(first).take(list_rows) {
+        let result = &results[index];
+        let mark = if marked[index] { '*' } else { ' ' };
+        let entry = format!("{}{}:{} {}", mark, result.file_path, result.line_number, result.line);
+        let text = text::truncate_to_width(&entry, cols as usize);
+        if row == selected {
+            term.write_line(&text.reversed().to_string())?;
+        } else {
+            term.write_line(text)?;
+        }
+    }
+
+    if preview_rows > 0 {
+        if let Some(&index) = visible.get(selected) {
+            for _ in visible.len().saturating_sub(first).min(list_rows)..list_rows {
+                term.write_line("")?;
+            }
+            preview.render(term, &results[index], preview_rows, cols as usize)?;
+        }
+    }
+    Ok(())
+}
+
+/// The lines of the most recently previewed file.
+#[derive(Default)]
+struct Preview {
+    path: String,
+    lines: Vec<String>,
+}
+
+impl Preview {
+    fn render(&mut self, term: &Term, result: &SearchResult, rows: usize, cols: usize) -> io::Result<()> {
+        if self.path != result.file_path {
+            let bytes = fs::read(&result.file_path).unwrap_or_default();
+            self.lines = String::from_utf8_lossy(&bytes).lines().map(str::to_string).collect();
+            self.path = result.file_path.clone();
+        }
+
+        let title = format!("── {} ", result.file_path);
+        let fill = cols.saturating_sub(text::display_width(&title));
+        term.write_line(&format!("{}{}", title, "─".repeat(fill)).dimmed().to_string())?;
+
+        let body = rows.saturating_sub(1);
+        let target = result.line_number.saturating_sub(1);
+        let first = target.saturating_sub(body / 2);
+        let syntax = highlight::detect(&result.file_path);
+        let mut state = State::default();
+        let number_width = (first + body).to_string().len();
+
+        // Replay earlier lines so block comments opened above are tracked.
+        if let Some(syntax) = syntax {
+            for line in self.lines.iter().take(first) {
+                highlight::highlight(line, syntax, &mut state, &[], &[]);
+            }
+        }
+
+        for (index, line) in self.lines.iter().enumerate().skip(first).take(body) {
+            let line = text::truncate_to_width(line, cols.saturating_sub(number_width + 1));
+            let matches: &[(usize, usize)] = if index == target { &result.matches } else { &[] };
+            let matches: Vec<_> = matches.iter().copied().filter(|&(_, e)| e <= line.len()).collect();
+            let text = match syntax {
+                Some(syntax) => highlight::highlight(line, syntax, &mut state, &matches, &result.terms),
+                None => line.to_string(),
+            };
+            let number = format!("{:>width$}", index + 1, width = number_width);
+            let number = if index == target { number.themed(theme::palette().line_number) } else { number.dimmed() };
+            term.write_line(&format!("{} {}", number, text))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use std::fmt::Write;
+
+use crate::text;
+use crate::SearchResult;
+
+/// Encodes `s` as a JSON string literal.
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Encodes one result as a JSON object. Each match span is given both as
+/// byte offsets (`start`/`end`, for binary tools) and as character offsets
+/// (`char_start`/`char_end`, for editors), all zero-based and end-exclusive.
+pub fn result(result: &SearchResult) -> String {
+    let spans: Vec<String> = result
+        .matches
+        .iter()
+        .map(|&(start, end)| format!("{{{}}}", span(&result.line, start, end)))
+        .collect();
+    format!("{{{},\"matches\":[{}]}}", location(result), spans.join(","))
+}
+
+/// Encodes match `index` of `result` as a flat JSON object: the location
+/// fields of `result` plus that match's span fields.
+pub fn single_match(result: &SearchResult, index: usize) -> String {
+    let (start, end) = result.spans()[index];
+    format!("{{{},{}}}", location(result), span(&result.line, start, end))
+}
+
+/// Binary matches also carry `byte_offset`, and their `line` is the
+/// matched bytes made printable. `--select` matches carry their element
+/// path as `origin`, and notebook matches their `cell` (from 1).
+fn location(result: &SearchResult) -> String {
+    let mut out = format!(
+        "\"path\":{},\"line_number\":{},\"line\":{}",
+        string(&result.file_path),
+        result.line_number,
+        string(&result.line)
+    );
+    if let Some(offset) = result.byte_offset {
+        let _ = write!(out, ",\"byte_offset\":{}", offset);
+    }
+    if let Some(origin) = &result.origin {
+        let _ = write!(out, ",\"origin\":{}", string(origin));
+    }
+    if let Some(cell) = result.cell {
+        let _ = write!(out, ",\"cell\":{}", cell);
+    }
+    out
+}
+
+fn span(line: &str, start: usize, end: usize) -> String {
+    let (start, end, char_start, char_end) = text::char_span(line, start, end);
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "\"start\":{},\"end\":{},\"char_start\":{},\"char_end\":{},\"text\":{}",
+        start,
+        end,
+        char_start,
+        char_end,
+        string(&line[start..end])
+    );
+    out
+}
+
+/// A parsed JSON value, with as much detail as fsearch's readers need:
+/// numbers, booleans and null are not kept.
+pub enum Value {
+    Other,
+    Str(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The value of `key`, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the JSON document `text`.
+pub fn parse(text: &str) -> Result<Value, String> {
+    Parser::new(text).document()
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Parser<'a> {
+        Parser { text: text.strip_prefix('\u{feff}').unwrap_or(text), pos: 0, line: 1 }
+    }
+
+    fn document(mut self) -> Result<Value, String> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        match self.pos == self.text.len() {
+            true => Ok(value),
+            false => Err(self.error("trailing characters")),
+        }
+    }
+
+    fn error(&self, what: &str) -> String {
+        format!("{} on line {}", what, self.line)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek().filter(u8::is_ascii_whitespace) {
+            if b == b'\n' {
+                self.line += 1;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() == Some(byte) {
+            true => {
+                self.pos += 1;
+                Ok(())
+            }
+            false => Err(self.error(&format!("expected '{}'", byte as char))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let Value::Str(key) = self.string()? else { unreachable!() };
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Value::Object(fields));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Value::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'"') => self.string(),
+            Some(b) if b == b'-' || b.is_ascii_alphanumeric() => {
+                // Numbers, true, false and null: nothing a search needs.
+                while self.peek().is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'+' | b'.')) {
+                    self.pos += 1;
+                }
+                Ok(Value::Other)
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<Value, String> {
+        if self.peek() != Some(b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            let Some(special) = rest.find(['"', '\\', '\n']) else { return Err(self.error("unterminated string")) };
+            out.push_str(&rest[..special]);
+            self.pos += special + 1;
+            match rest.as_bytes()[special] {
+                b'"' => return Ok(Value::Str(out)),
+                b'\n' => return Err(self.error("newline in string")),
+                _ => {}
+            }
+            let escape = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match escape {
+                b'n' => out.push('\n'),
+                b't' => out.push('\t'),
+                b'r' => out.push('\r'),
+                b'b' => out.push('\u{8}'),
+                b'f' => out.push('\u{c}'),
+                b'u' => {
+                    let high = self.hex4()?;
+                    let c = if (0xD800..0xDC00).contains(&high) && self.text[self.pos..].starts_with("\\u") {
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF))
+                    } else {
+                        char::from_u32(high)
+                    };
+                    out.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                other => out.push(other as char),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.pos..self.pos + 4).ok_or_else(|| self.error("short \\u escape"))?;
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| self.error("bad \\u escape"))
+    }
+}
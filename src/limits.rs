@@ -0,0 +1,29 @@
+use clap::ValueEnum;
+
+/// What to do with lines longer than `--max-line-length`.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LongLines {
+    /// Skip the whole file without reporting it
+    Skip,
+    /// Search and print only the first N bytes of the line
+    Truncate,
+}
+
+/// Applies the `--max-line-length` policy to `line`. Returns `None` when the
+/// file should be skipped, otherwise the part of the line to search.
+pub fn clip(line: &str, max: Option<usize>, policy: LongLines) -> Option<&str> {
+    let Some(max) = max else { return Some(line) };
+    if line.len() <= max {
+        return Some(line);
+    }
+    match policy {
+        LongLines::Skip => None,
+        LongLines::Truncate => {
+            let mut end = max;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            Some(&line[..end])
+        }
+    }
+}
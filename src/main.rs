@@ -1,83 +1,966 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fs;
+use std::num::NonZeroUsize;
 use std::path::Path;
-use std::io::{self, BufRead};
-use regex::Regex;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use std::io::{self, BufRead, BufReader, Read};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use memmap2::Mmap;
 
+mod aggregate;
+mod binary;
+mod config;
+mod context;
+mod csv;
+mod diff;
+mod docs;
+mod encoding;
+mod fields;
+mod fuzzy;
+mod git;
+mod glob;
+mod heuristics;
+mod highlight;
+mod i18n;
+mod ignore;
+mod imports;
+mod interactive;
+mod json;
+mod limits;
+mod markup;
+mod meta;
+mod notebook;
+mod output;
+mod paths;
+mod pattern;
+mod profile;
+mod replace;
+mod sample;
+mod section;
+mod sarif;
+mod sink;
+mod sourcemap;
+mod spill;
+mod symbols;
+mod template;
+mod testcode;
+mod text;
+mod terminal;
+mod theme;
+mod todos;
+mod types;
+mod walk;
+
+use aggregate::{Agg, SortSpec, Sorter};
+use encoding::{Bom, EncodingErrors};
+use fields::FieldSpec;
+use glob::{Glob, GlobFilter};
+use highlight::Scope;
+use limits::LongLines;
+use meta::MetaField;
+use output::{Format, Printer};
+use markup::Selector;
+use pattern::{AllOf, Matcher};
+use paths::PathStyle;
+use profile::{FileProfile, Profiler};
+use replace::Replacer;
+use sample::Reservoir;
+use section::Sections;
+use sink::Sink;
+use spill::ResultBuffer;
+use symbols::SymbolKind;
+use template::Template;
+use terminal::ColorChoice;
+use theme::{Background, Palette, Theme, Themed};
+use todos::{TodoFormat, TodoGroup};
+use types::TypeAdd;
+use walk::{WalkOptions, WalkOrder};
+
 const LARGE_FILE_THRESHOLD: u64 = 10_000_000; // 10MB
+const RESULT_CHANNEL_CAPACITY: usize = 256; // per-file result batches in flight
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(name = "fsearch", author, version, about, long_about = None, args_override_self = true)]
+#[command(after_help = "Commands:\n  fsearch gen-man        Print a man page\n  fsearch explain FLAG   Describe one option in detail\n  fsearch explain-pattern -t TERM [OPTIONS]\n                         Show how the search pattern is compiled\n  fsearch symbols [--kind KIND] NAME [OPTIONS]\n                         Find definitions whose name matches NAME\n  fsearch imports MODULE [OPTIONS]\n                         Count the import statements of MODULE in each file\n  fsearch todos [--by GROUP] [--report FORMAT] [OPTIONS]\n                         Report TODO, FIXME, HACK and XXX annotations")]
+#[group(skip)]
 struct Args {
     /// Directory to search in (defaults to current directory)
     #[arg(short, long, default_value = ".")]
     directory: String,
 
-    /// File extension to search (if not specified, searches all files)
-    #[arg(short, long)]
-    extension: Option<String>,
+    /// File extensions to search, e.g. 'rs,toml,md' or repeated, including
+    /// multi-part ones like 'd.ts'; matched case-insensitively (if not
+    /// specified, searches all files)
+    #[arg(short, long, value_delimiter = ',', value_parser = walk::parse_extension)]
+    extension: Vec<String>,
+
+    /// Term to search for (supports regex); repeat to search for several at
+    /// once
+    #[arg(short, long, required_unless_present_any = ["pattern_file", "near", "type_list", "operand"])]
+    term: Vec<String>,
+
+    /// With several terms, require all of them to match on the same line or
+    /// anywhere in the same file
+    #[arg(long, value_enum, value_name = "SCOPE")]
+    all_of: Option<AllOf>,
+
+    /// Report only files with a match for every term, anywhere in the file
+    /// (the same as --all-of file)
+    #[arg(long, default_value_t = false, conflicts_with = "all_of")]
+    file_matches_all: bool,
+
+    /// Search for A and B, reporting only the lines where one of them has the
+    /// other within --within lines
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["term", "pattern_file", "all_of", "file_matches_all", "invert_match"])]
+    near: Vec<String>,
+
+    /// Treat files as XML/HTML and only search the text of elements matching
+    /// SELECTOR: names, '*', '.class' and '#id' joined by spaces
+    /// (descendant) or '>' (child), e.g. 'div.note > p'; end with '@attr'
+    /// to search that attribute instead. Each line shows its element path
+    #[arg(long, value_parser = markup::parse_selector, value_name = "SELECTOR", conflicts_with_all = ["invert_match", "section"])]
+    select: Option<Selector>,
+
+    /// Search only the cell sources of Jupyter notebooks (.ipynb), not the
+    /// text their outputs left behind
+    #[arg(long, default_value_t = false)]
+    no_notebook_outputs: bool,
+
+    /// Only search the lines of this INI/TOML section, from its '[NAME]'
+    /// header to the next header; may be repeated
+    #[arg(long, value_parser = section::parse_section, value_name = "NAME")]
+    section: Vec<String>,
+
+    /// With 'fsearch symbols', only report definitions of these kinds; may
+    /// be repeated or comma-separated
+    #[arg(long, value_enum, value_delimiter = ',')]
+    kind: Vec<SymbolKind>,
+
+    /// With 'fsearch todos', how to group the report (by file unless given)
+    #[arg(long = "by", value_enum, value_name = "GROUP")]
+    todo_by: Option<TodoGroup>,
+
+    /// With 'fsearch todos', how to write the report (text unless given)
+    #[arg(long = "report", value_enum, value_name = "FORMAT")]
+    todo_format: Option<TodoFormat>,
+
+    /// The NAME of 'fsearch symbols' or MODULE of 'fsearch imports'
+    #[arg(hide = true, value_name = "NAME")]
+    operand: Option<String>,
+
+    /// Run as 'fsearch symbols', set before parsing
+    #[arg(skip)]
+    symbols: bool,
+
+    /// Run as 'fsearch imports', set before parsing
+    #[arg(skip)]
+    imports: bool,
+
+    /// Run as 'fsearch todos', set before parsing
+    #[arg(skip)]
+    todos: bool,
+
+    /// Only match inside comments, inside string literals, or in the code
+    /// outside both; files in languages fsearch can't tokenize are skipped
+    #[arg(long, value_enum, conflicts_with = "select")]
+    scope: Option<Scope>,
+
+    /// How many lines apart --near terms may be
+    #[arg(long, default_value_t = 5, value_name = "N", requires = "near")]
+    within: usize,
 
-    /// Term to search for (supports regex)
-    #[arg(short, long)]
-    term: String,
+    /// Also search for each line of FILE as a term; blank lines and lines
+    /// starting with '#' are skipped
+    #[arg(short = 'f', long, value_name = "FILE")]
+    pattern_file: Vec<String>,
 
     /// Search recursively in subdirectories
     #[arg(short, long, default_value_t = true)]
     recursive: bool,
 
+    /// Follow symbolic links to directories; links back into a directory
+    /// being searched are skipped, and files reached twice are searched once
+    #[arg(short = 'L', long, default_value_t = false)]
+    follow: bool,
+
+    /// Descend at most NUM directory levels below --directory (0 searches
+    /// only the files directly in it)
+    #[arg(long, value_name = "NUM")]
+    max_depth: Option<usize>,
+
     /// Case sensitive search
-    #[arg(short = 'c', long, default_value_t = false)]
+    #[arg(short = 'c', long, default_value_t = false, overrides_with_all = ["ignore_case", "smart_case"])]
     case_sensitive: bool,
 
+    /// Case insensitive search, even where --smart-case would apply
+    #[arg(long, default_value_t = false, overrides_with_all = ["case_sensitive", "smart_case"])]
+    ignore_case: bool,
+
+    /// Case sensitive if any term has an uppercase letter, insensitive
+    /// otherwise (the default with --interactive)
+    #[arg(short = 'S', long, default_value_t = false, overrides_with_all = ["case_sensitive", "ignore_case"])]
+    smart_case: bool,
+
     /// Use regex for searching
     #[arg(short = 'x', long, default_value_t = false)]
     regex: bool,
 
-    /// Number of threads for parallel search
-    #[arg(short = 't', long, default_value_t = 4)]
-    threads: usize,
+    /// Match only whole words, so 'log' doesn't match 'catalog' or 'login'
+    #[arg(short = 'w', long, default_value_t = false)]
+    word: bool,
+
+    /// Also match text up to EDITS insertions, deletions or substitutions
+    /// away from a term, so 'recieve' finds 'receive' (1 edit if no number
+    /// is given)
+    #[arg(long, value_name = "EDITS", num_args = 0..=1, default_missing_value = "1", conflicts_with_all = ["regex", "word"])]
+    fuzzy: Option<usize>,
+
+    /// Number of threads matching file contents (defaults to the number of CPUs)
+    #[arg(short = 'j', long, visible_alias = "threads")]
+    search_threads: Option<usize>,
+
+    /// Number of threads reading directories (defaults to half the CPUs)
+    #[arg(long)]
+    walk_threads: Option<usize>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Print one path:line:column:text row per match, for ':cexpr' and
+    /// ':grep' in Vim (the same as --format vimgrep)
+    #[arg(long, default_value_t = false, conflicts_with = "format")]
+    vimgrep: bool,
+
+    /// Output template per match, e.g. '{path}\t{line}\t{mtime}\t{matched}'
+    /// (variables: path, line, column, text, matched, size, mtime, owner)
+    #[arg(long)]
+    template: Option<String>,
+
+    /// Report how many distinct matched values were found
+    #[arg(long, default_value_t = false)]
+    count_unique: bool,
+
+    /// List each distinct value with its count (implies --count-unique)
+    #[arg(long, default_value_t = false)]
+    list_unique: bool,
+
+    /// Capture group (name or index) used as the matched value for aggregations
+    #[arg(long)]
+    capture: Option<String>,
+
+    /// Bucket matches by a capture group (name or index) and print one row per bucket
+    #[arg(long)]
+    group_by: Option<String>,
+
+    /// Aggregate computed per --group-by bucket (numeric aggregates use the --capture value)
+    #[arg(long, value_enum, default_value_t = Agg::Count)]
+    agg: Agg,
+
+    /// Order results by a capture group value: 'capture:NAME[:numeric]'
+    #[arg(long, value_parser = aggregate::parse_sort_spec)]
+    sort_by: Option<SortSpec>,
+
+    /// Print only selected fields of each matching line: 'SEP:1,3' or 'SEP:2-4'
+    /// (use ' :LIST' to split on whitespace, '\t:LIST' for tabs)
+    #[arg(long, value_parser = fields::parse_field_spec)]
+    fields: Option<FieldSpec>,
+
+    /// How to handle files that are not valid UTF-8
+    #[arg(long, value_enum, default_value_t = EncodingErrors::Skip)]
+    encoding_errors: EncodingErrors,
+
+    /// Longest line, in bytes, searched normally (see --long-lines)
+    #[arg(long)]
+    max_line_length: Option<usize>,
+
+    /// What to do with files containing lines longer than --max-line-length
+    #[arg(long, value_enum, default_value_t = LongLines::Skip)]
+    long_lines: LongLines,
+
+    /// Report only the first matching line of each file
+    #[arg(long, default_value_t = false)]
+    first_match: bool,
+
+    /// Also search binary files (a NUL byte in the first 8 KiB), reporting
+    /// the byte offset of each match; they are skipped by default
+    #[arg(long, default_value_t = false)]
+    binary: bool,
+
+    /// Search binary files as if they were text, replacing invalid UTF-8
+    /// as --encoding-errors lossy does
+    #[arg(short = 'a', long, default_value_t = false, conflicts_with = "binary")]
+    text: bool,
+
+    /// Follow each match in a binary file with a hex dump of the bytes
+    /// around it, NUM bytes either side (implies --binary)
+    #[arg(long, value_name = "NUM", conflicts_with = "text")]
+    hex_context: Option<usize>,
+
+    /// Report the lines that don't match instead
+    #[arg(short = 'v', long, default_value_t = false)]
+    invert_match: bool,
+
+    /// Print only the paths of files with a match, one per line
+    #[arg(short = 'l', long, default_value_t = false, conflicts_with_all = ["format", "vimgrep", "template", "fields", "count", "count_only_dirs"])]
+    files_with_matches: bool,
+
+    /// Print only the paths of files without a match, one per line (binary
+    /// files aren't searched, so aren't listed, without --binary or -a)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["files_with_matches", "format", "vimgrep", "template", "fields", "count", "count_only_dirs"])]
+    files_without_match: bool,
+
+    /// Also search minified scripts and stylesheets (skipped by default)
+    #[arg(long, default_value_t = false)]
+    search_minified: bool,
+
+    /// Translate matches in scripts and stylesheets that have a source map
+    /// back to the original source, shown next to the bundle location;
+    /// implies --search-minified
+    #[arg(long, default_value_t = false)]
+    resolve_sourcemaps: bool,
+
+    /// Also search generated files: lockfiles, source maps, protobuf output
+    /// and files marked '@generated' (skipped by default)
+    #[arg(long, default_value_t = false)]
+    include_generated: bool,
+
+    /// Also descend into vendor/, third_party/, node_modules/, .venv/ and target/
+    /// (skipped by default)
+    #[arg(long, default_value_t = false)]
+    include_vendored: bool,
+
+    /// Also search files that .gitignore, .git/info/exclude or the global
+    /// git excludes file ignore
+    #[arg(long, default_value_t = false)]
+    no_ignore: bool,
+
+    /// Also search hidden files and directories: dotfiles, and on Windows
+    /// those with the hidden attribute (skipped by default, though a hidden
+    /// --directory is still searched). `.git` is skipped either way unless
+    /// it is the --directory
+    #[arg(long, default_value_t = false)]
+    hidden: bool,
+
+    /// Order in which files are searched and reported
+    #[arg(long, value_enum, default_value_t = WalkOrder::Dfs)]
+    walk_order: WalkOrder,
+
+    /// Report matches in hard-linked files under every link path (the content
+    /// is still searched once)
+    #[arg(long, default_value_t = false)]
+    all_links: bool,
+
+    /// Stop at the first unreadable directory instead of warning and skipping it
+    #[arg(long, default_value_t = false)]
+    strict_walk: bool,
+
+    /// Skip files and directories matching a glob, e.g. '*_test.go' or 'tests/**';
+    /// may be repeated, and wins over --extension when both apply
+    #[arg(long, value_parser = glob::parse_glob)]
+    glob_not: Vec<Glob>,
+
+    /// Only search files matching a glob, or with a leading '!' skip those
+    /// matching it; may be repeated, e.g. -g 'src/**/*.rs' -g '!*_test.rs'.
+    /// A glob with a '/' matches the whole path under --directory. Files
+    /// must match one of the include globs and --extension
+    #[arg(short, long, value_parser = glob::parse_glob_filter, value_name = "GLOB")]
+    glob: Vec<GlobFilter>,
+
+    /// Only search test code: files named or placed like tests (*_test.go,
+    /// test_*.py, *.spec.ts, FooTest.java, tests/ ...) and Rust
+    /// #[cfg(test)] and #[test] items
+    #[arg(long, default_value_t = false, conflicts_with = "no_tests")]
+    tests_only: bool,
+
+    /// Leave out test code, as recognized by --tests-only
+    #[arg(long, default_value_t = false)]
+    no_tests: bool,
+
+    /// Only search files of these types, e.g. 'rust' or 'web'; may be
+    /// repeated or comma-separated (see --type-list)
+    #[arg(long = "type", value_delimiter = ',', value_name = "TYPE")]
+    file_type: Vec<String>,
+
+    /// Skip files of these types; may be repeated or comma-separated
+    #[arg(long, value_delimiter = ',', value_name = "TYPE")]
+    type_not: Vec<String>,
+
+    /// Define a file type, or add globs to one, as NAME:GLOB[,GLOB...],
+    /// e.g. 'web:*.astro'; may be repeated
+    #[arg(long, value_parser = types::parse_type_add, value_name = "NAME:GLOBS")]
+    type_add: Vec<TypeAdd>,
+
+    /// List the file types --type knows and their globs, then exit
+    #[arg(long, default_value_t = false)]
+    type_list: bool,
+
+    /// The globs of --type, resolved after parsing
+    #[arg(skip)]
+    type_globs: Vec<Glob>,
+
+    /// The globs of --type-not, resolved after parsing
+    #[arg(skip)]
+    type_not_globs: Vec<Glob>,
+
+    /// How to print file paths (defaults to the paths as discovered under --directory)
+    #[arg(long, value_enum)]
+    path_style: Option<PathStyle>,
+
+    /// Append file metadata to each result, e.g. 'size,mtime,owner'
+    #[arg(long, value_enum, value_delimiter = ',')]
+    show_meta: Vec<MetaField>,
+
+    /// Report time spent per phase and the slowest files on stderr
+    #[arg(long, default_value_t = false)]
+    profile: bool,
+
+    /// Number of slowest files listed by --profile
+    #[arg(long, default_value_t = 10)]
+    profile_top: usize,
+
+    /// Results held in memory when sorting or aggregating before spilling to temporary files
+    #[arg(long, default_value_t = 1_000_000)]
+    spill_threshold: usize,
+
+    /// Pause after every N results and wait for a key (only when stdout is a terminal)
+    #[arg(long)]
+    page_size: Option<usize>,
+
+    /// Browse results in a full-screen list that filters as you type; with
+    /// --replace, confirm each replacement instead
+    #[arg(short = 'i', long, default_value_t = false)]
+    interactive: bool,
+
+    /// Write the results chosen in --interactive to this file instead of stdout
+    #[arg(long, requires = "interactive")]
+    export: Option<String>,
+
+    /// Only report matches on lines added or changed by this git diff range,
+    /// e.g. 'main..HEAD' (anything 'git diff' accepts)
+    #[arg(long, value_name = "RANGE")]
+    changed_in: Option<String>,
+
+    /// Show the enclosing function or block of each match as context
+    /// (found from indentation, so it works for most languages)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["after_context", "before_context", "context"])]
+    context_block: bool,
+
+    /// Show NUM lines after each match
+    #[arg(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
+
+    /// Show NUM lines before each match
+    #[arg(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
+
+    /// Show NUM lines before and after each match (-A and -B take precedence)
+    #[arg(short = 'C', long, value_name = "NUM")]
+    context: Option<usize>,
+
+    /// Follow each file's results with a match count, and end with a grand total
+    #[arg(long, default_value_t = false)]
+    summary: bool,
+
+    /// Don't print line numbers in results
+    #[arg(long, default_value_t = false)]
+    no_line_number: bool,
+
+    /// Text between the path and line number of each result
+    #[arg(long, default_value = ":", value_name = "SEP")]
+    separator: String,
+
+    /// Right-align line numbers to at least this many columns
+    #[arg(long, default_value_t = 0, value_name = "WIDTH")]
+    line_number_width: usize,
+
+    /// Show only N characters of each matching line, centered on its first
+    /// match, with '…' where the line was cut
+    #[arg(long, value_name = "N")]
+    snippet_chars: Option<NonZeroUsize>,
+
+    /// Print each file's path once as a heading above its matching lines
+    /// (the default when writing to a terminal)
+    #[arg(long, default_value_t = false, overrides_with = "no_heading")]
+    heading: bool,
+
+    /// Print the path on every matching line, even on a terminal
+    #[arg(long, default_value_t = false, overrides_with = "heading")]
+    no_heading: bool,
+
+    /// Print a random sample of N matching lines, drawn uniformly from all
+    /// of them; the closing count still reports every matching line
+    #[arg(long, value_name = "N", conflicts_with = "interactive")]
+    sample: Option<usize>,
+
+    /// Rewrite matching files in place, replacing each match with TEMPLATE
+    /// ($1 or ${name} insert capture groups), and print what changed
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["invert_match", "fuzzy", "files_without_match"])]
+    replace: Option<String>,
+
+    /// With --replace, keep each original file as PATH+SUFFIX
+    #[arg(long, value_name = "SUFFIX", requires = "replace")]
+    backup_suffix: Option<String>,
+
+    /// With --replace, print a unified diff of the changes (ready for
+    /// `patch -p0`) instead of writing them
+    #[arg(long, default_value_t = false, requires = "replace", conflicts_with = "backup_suffix")]
+    diff: bool,
+
+    /// Print only the number of matching lines in each file, and the total
+    #[arg(long, default_value_t = false, conflicts_with_all = ["format", "vimgrep", "template", "fields", "count_only_dirs"])]
+    count: bool,
+
+    /// Also send each match to 'syslog' or 'webhook:http://HOST[:PORT]/PATH'
+    /// (one POST of JSON Lines per run; plain HTTP only, not https://, and
+    /// IPv6 hosts in brackets); may be repeated
+    #[arg(long, value_parser = sink::parse_sink, value_name = "SINK", conflicts_with_all = ["group_by", "agg", "count_unique", "list_unique", "replace", "interactive"])]
+    sink: Vec<Sink>,
+
+    /// Print only the top-level directories under --directory that have
+    /// matches, with their counts, instead of the matching lines
+    #[arg(long, default_value_t = false, conflicts_with_all = ["format", "vimgrep", "template", "fields", "path_style"])]
+    count_only_dirs: bool,
+
+    /// Syntax-highlight the content of each result line, not just the match
+    #[arg(long, default_value_t = false)]
+    pretty: bool,
+
+    /// Terminal background, used to pick readable colors
+    #[arg(long, value_enum, default_value_t = Background::Auto)]
+    background: Background,
+
+    /// Color preset (defaults to dark or light based on --background)
+    #[arg(long, value_enum)]
+    theme: Option<Theme>,
+
+    /// When to use colors
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
 }
 
-#[derive(Debug)]
+impl Args {
+    /// Whether only a file's first match is wanted.
+    fn first_match_only(&self) -> bool {
+        self.first_match || self.files_with_matches || self.files_without_match
+    }
+
+    /// Whether each file can stop being searched after its first match.
+    /// `--all-of file` and `--near` have to see every term first.
+    fn stop_at_first_match(&self) -> bool {
+        self.first_match_only() && self.all_of != Some(AllOf::File) && self.near.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
 struct SearchResult {
     file_path: String,
     line_number: usize,
     line: String,
     matches: Vec<(usize, usize)>, // start and end positions of matches
+    byte_offset: Option<usize>, // where the match starts, for binary files
+    terms: Vec<usize>, // which --term produced each match
+    origin: Option<String>, // where in a structured file the line is, e.g. an element path
+    cell: Option<usize>, // the notebook cell (from 1) the line is in; line_number then counts lines of its text
+    context: Vec<(usize, String)>, // numbered lines shown around it, for -A/-B/-C and --context-block
+}
+
+impl SearchResult {
+    /// The match spans, or one empty span for a line reported by
+    /// `--invert-match`, so formats with a row per match still print it.
+    fn spans(&self) -> &[(usize, usize)] {
+        if self.matches.is_empty() {
+            &[(0, 0)]
+        } else {
+            &self.matches
+        }
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    // Config files and then FSEARCH_* variables supply default flags ahead
+    // of the real ones, so later layers override earlier ones.
+    let mut cli: Vec<OsString> = std::env::args_os().collect();
+    let mut explain_pattern = false;
+    let mut symbols = false;
+    let mut imports = false;
+    let mut todo_report = false;
+    match cli.get(1).and_then(|arg| arg.to_str()) {
+        Some("gen-man") => {
+            print!("{}", docs::man_page(Args::command()));
+            return;
+        }
+        Some("explain") => {
+            let flag = cli.get(2).map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+            match docs::explain(Args::command(), &flag) {
+                Some(text) => print!("{}", text),
+                None => {
+                    eprintln!("{}", i18n::error(i18n::tr("unknown-flag", &[("flag", &flag)])).red());
+                    std::process::exit(2);
+                }
+            }
+            return;
+        }
+        Some("explain-pattern") => {
+            cli.remove(1);
+            explain_pattern = true;
+        }
+        Some("symbols") => {
+            cli.remove(1);
+            symbols = true;
+        }
+        Some("imports") => {
+            cli.remove(1);
+            imports = true;
+        }
+        Some("todos") => {
+            // Ahead of the user's flags, so theirs win (-i, say).
+            cli.splice(1..2, todos::FLAGS.map(OsString::from));
+            todo_report = true;
+        }
+        _ => {}
+    }
+    let defaults = config::load(&config::directory_arg(&cli))
+        .and_then(|mut flags| {
+            flags.extend(config::env_flags()?);
+            Ok(flags)
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("{}", i18n::error(i18n::tr("invalid-config", &[("detail", &e)])).red());
+            std::process::exit(2);
+        });
+    let (program, rest) = cli.split_first().map_or((None, &[][..]), |(p, rest)| (Some(p), rest));
+    let mut args = Args::parse_from(program.cloned().into_iter().chain(defaults).chain(rest.iter().cloned()));
+    if args.type_list {
+        print!("{}", types::list(&args.type_add));
+        return;
+    }
+    match args.operand.take() {
+        Some(name) if symbols || imports => args.term.insert(0, name),
+        Some(name) => Args::command().error(ErrorKind::UnknownArgument, format!("unexpected argument '{}' found", name)).exit(),
+        None => {}
+    }
+    let misplaced = [
+        ("--kind", "symbols", !args.kind.is_empty() && !symbols),
+        ("--by", "todos", args.todo_by.is_some() && !todo_report),
+        ("--report", "todos", args.todo_format.is_some() && !todo_report),
+    ];
+    if let Some((flag, command, _)) = misplaced.iter().find(|(_, _, misplaced)| *misplaced) {
+        let message = format!("'{}' only applies to 'fsearch {}'", flag, command);
+        Args::command().error(ErrorKind::ArgumentConflict, message).exit();
+    }
+    // The todos report isn't made of match events.
+    if todo_report && !args.sink.is_empty() {
+        Args::command().error(ErrorKind::ArgumentConflict, "'--sink' can't be used with 'fsearch todos'").exit();
+    }
+    args.symbols = symbols;
+    args.imports = imports;
+    args.todos = todo_report;
+    // 'fsearch imports' lists files with their counts unless asked for
+    // another output.
+    let other_output = args.summary || args.files_with_matches || args.count_only_dirs || args.interactive;
+    if imports && args.format == Format::Text && args.template.is_none() && args.fields.is_none() && !other_output {
+        args.count = true;
+    }
+    if args.vimgrep {
+        args.format = Format::Vimgrep;
+    }
+    if args.text && args.encoding_errors == EncodingErrors::Skip {
+        args.encoding_errors = EncodingErrors::Lossy;
+    }
+    match (types::globs(&args.file_type, &args.type_add), types::globs(&args.type_not, &args.type_add)) {
+        (Ok(globs), Ok(not_globs)) => {
+            args.type_globs = globs;
+            args.type_not_globs = not_globs;
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(2);
+        }
+    }
+    if args.file_matches_all {
+        args.all_of = Some(AllOf::File);
+    }
+    if !args.near.is_empty() {
+        args.term = args.near.clone();
+    }
+    for path in &args.pattern_file {
+        match pattern::read_pattern_file(path) {
+            Ok(terms) => args.term.extend(terms),
+            Err(e) => {
+                eprintln!("{}", i18n::error(i18n::tr("invalid-pattern", &[("detail", &e)])).red());
+                std::process::exit(2);
+            }
+        }
+    }
+    let browsing = args.interactive && args.replace.is_none();
+    if args.smart_case || (browsing && !args.case_sensitive && !args.ignore_case) {
+        args.case_sensitive = args.term.iter().any(|term| pattern::has_uppercase(term, args.regex));
+    }
+
+    terminal::init();
+    terminal::set_color(args.color);
+    if colored::control::SHOULD_COLORIZE.should_colorize() {
+        let theme = args.theme.unwrap_or_else(|| match theme::detect_background(args.background) {
+            Background::Light => Theme::Light,
+            _ => Theme::Dark,
+        });
+        theme::init(Palette::for_theme(theme));
+    }
 
     rayon::ThreadPoolBuilder::new()
-        .num_threads(args.threads)
+        .num_threads(args.search_threads.unwrap_or_else(available_cpus))
         .build_global()
         .unwrap();
 
-    let pattern = if args.regex {
-        Regex::new(&args.term).expect("Invalid regex pattern")
+    if explain_pattern {
+        match pattern::explain(&args) {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("{}", i18n::error(i18n::tr("invalid-pattern", &[("detail", &e)])).red());
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+    let pattern = pattern::compile(&args).unwrap_or_else(|e| {
+        eprintln!("{}", i18n::error(i18n::tr("invalid-pattern", &[("detail", &e)])).red());
+        std::process::exit(2);
+    });
+
+    let template = args.template.as_deref().map(|t| {
+        Template::parse(t).unwrap_or_else(|e| {
+            eprintln!("{}", i18n::error(i18n::tr("invalid-template", &[("detail", &e)])).red());
+            std::process::exit(2);
+        })
+    });
+
+    let resolve = |spec: &String| {
+        aggregate::resolve_capture(pattern.regex(), spec).unwrap_or_else(|e| {
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(2);
+        })
+    };
+    let capture_group = args.capture.as_ref().map_or(0, resolve);
+    let group_by = args.group_by.as_ref().map(resolve);
+    let sort_by = args.sort_by.as_ref().map(|spec| (resolve(&spec.capture), spec.numeric));
+
+    let profiler = Profiler::new(args.profile);
+    let aggregating = group_by.is_some() || args.agg != Agg::Count || args.count_unique || args.list_unique;
+    let mut output_time = Duration::ZERO;
+
+    // Sorting, aggregation, paging (which shows the remaining count) and the
+    // interactive browser need the whole result set; everything else is printed as soon as each file's
+    // results are ready.
+    let paging = args.page_size.is_some() && console::Term::stdout().is_term();
+    if args.interactive && args.replace.is_none() && !console::Term::stdout().is_term() {
+        eprintln!("{}", i18n::error(i18n::tr("interactive-needs-tty", &[])).red());
+        std::process::exit(2);
+    }
+    if let Some(template) = &args.replace {
+        let mut replacer = Replacer::new(pattern.regex(), template, &args);
+        let searched = search_files(&args.directory, &args.extension, &pattern, &args, &profiler, |batch| {
+            profiler.time(&mut output_time, || replacer.apply(&batch))
+        });
+        if let Err(e) = searched {
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(2);
+        }
+        replacer.finish();
+    } else if args.todos {
+        let mut found = Vec::new();
+        let searched = search_files(&args.directory, &args.extension, &pattern, &args, &profiler, |batch| {
+            found.extend(todos::parse(&batch));
+        });
+        if let Err(e) = searched {
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(2);
+        }
+        let by = args.todo_by.unwrap_or(TodoGroup::File);
+        if by == TodoGroup::Author {
+            todos::attribute(&mut found);
+        }
+        print!("{}", todos::report(&found, by, args.todo_format.unwrap_or(TodoFormat::Text)));
+    } else if aggregating || sort_by.is_some() || paging || args.interactive {
+        let sorter = sort_by.map(|(group, numeric)| Sorter { pattern: pattern.regex(), group, numeric });
+        let (total, results) = match buffer_results(&pattern, &args, &profiler, sorter.as_ref()) {
+            Ok(buffered) => buffered,
+            Err(e) => {
+                eprintln!("{}", i18n::error(e).red());
+                std::process::exit(2);
+            }
+        };
+
+        let output_start = Instant::now();
+        if group_by.is_some() || args.agg != Agg::Count {
+            aggregate::display_grouped(results, pattern.regex(), group_by, capture_group, &args);
+        } else if args.count_unique || args.list_unique {
+            aggregate::display_unique(results, pattern.regex(), capture_group, &args);
+        } else if args.interactive {
+            if let Err(e) = interactive::browse(results.collect(), &args, template.as_ref()) {
+                eprintln!("{}", i18n::error(e).red());
+            }
+        } else {
+            let mut printer = Printer::new(&args, template.as_ref());
+            let (total, results) = match args.sample {
+                Some(size) => {
+                    let (sample, sampled_from) = sample_results(results, size);
+                    printer.sampled(sampled_from);
+                    (sample.len(), Box::new(sample.into_iter()) as Box<dyn Iterator<Item = SearchResult>>)
+                }
+                None => (total, results),
+            };
+            if paging {
+                printer.paginate(args.page_size.unwrap_or(1), total);
+            }
+            for result in results {
+                printer.print(std::slice::from_ref(&result));
+            }
+            printer.finish();
+        }
+        output_time = output_start.elapsed();
     } else {
-        Regex::new(&regex::escape(&args.term)).unwrap()
+        let mut printer = Printer::new(&args, template.as_ref());
+        // With --sample, results are kept in the reservoir and printed at the end.
+        let mut reservoir = args.sample.map(Reservoir::new);
+        let searched = search_files(&args.directory, &args.extension, &pattern, &args, &profiler, |batch| {
+            match &mut reservoir {
+                Some(reservoir) => batch.into_iter().for_each(|result| reservoir.offer(result)),
+                None => profiler.time(&mut output_time, || printer.print(&batch)),
+            }
+        });
+        if let Err(e) = searched {
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(2);
+        }
+        if let Some(reservoir) = reservoir {
+            printer.sampled(reservoir.offered());
+            let sample = reservoir.into_sorted();
+            profiler.time(&mut output_time, || printer.print(&sample));
+        }
+        printer.finish();
+    }
+
+    profiler.report(output_time, args.profile_top);
+}
+
+/// Keeps the `--near` results where a line matching one term has a line
+/// matching the other at most `within` lines away (or is that line).
+fn keep_near(results: &mut Vec<SearchResult>, pattern: &Matcher, within: usize) {
+    // Which terms each line has, counting one that overlaps the other's match.
+    let present: Vec<Vec<usize>> = results.iter().map(|r| pattern.terms_in(&r.line)).collect();
+    let lines_of = |term: usize| -> Vec<usize> {
+        results.iter().zip(&present).filter(|(_, terms)| terms.contains(&term)).map(|(r, _)| r.line_number).collect()
     };
+    let lines = [lines_of(0), lines_of(1)];
+    let close = |term: usize, line: usize| {
+        let others = &lines[1 - term];
+        let first = others.partition_point(|&other| other + within < line);
+        others.get(first).is_some_and(|&other| other <= line + within)
+    };
+    let mut present = present.into_iter();
+    results.retain(|r| present.next().unwrap_or_default().into_iter().any(|term| close(term, r.line_number)));
+}
 
-    match search_files(&args.directory, &args.extension, &pattern, &args) {
-        Ok(results) => display_results(results),
-        Err(e) => eprintln!("{}", format!("Error: {}", e).red()),
+/// Draws `size` results uniformly from `results`, returning them in their
+/// original order along with how many results there were.
+fn sample_results(results: impl Iterator<Item = SearchResult>, size: usize) -> (Vec<SearchResult>, usize) {
+    let mut reservoir = Reservoir::new(size);
+    results.for_each(|result| reservoir.offer(result));
+    let total = reservoir.offered();
+    (reservoir.into_sorted(), total)
+}
+
+/// Runs the search, keeping every result (spilling to disk past
+/// `--spill-threshold`) for modes that need the complete set.
+fn buffer_results<'a>(
+    pattern: &Matcher,
+    args: &Args,
+    profiler: &Profiler,
+    sorter: Option<&'a Sorter<'a>>,
+) -> io::Result<(usize, Box<dyn Iterator<Item = SearchResult> + 'a>)> {
+    let mut buffer = ResultBuffer::new(args.spill_threshold, sorter);
+    let mut spill_error = None;
+    search_files(&args.directory, &args.extension, pattern, args, profiler, |batch| {
+        if spill_error.is_none() {
+            spill_error = buffer.extend(batch).err();
+        }
+    })?;
+    if let Some(e) = spill_error {
+        return Err(e);
     }
+    Ok((buffer.len(), buffer.into_results()?))
+}
+
+fn available_cpus() -> usize {
+    std::thread::available_parallelism().map_or(4, |n| n.get())
 }
 
 fn search_files(
     directory: &str,
-    file_extension: &Option<String>,
-    pattern: &Regex,
+    file_extensions: &[String],
+    pattern: &Matcher,
     args: &Args,
-) -> io::Result<Vec<SearchResult>> {
+    profiler: &Profiler,
+    mut sink: impl FnMut(Vec<SearchResult>),
+) -> io::Result<()> {
+    let changed = match &args.changed_in {
+        Some(range) => Some(git::ChangedLines::from_git(directory, range).map_err(io::Error::other)?),
+        None => None,
+    };
+
+    let walk_start = Instant::now();
+    let walk_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.walk_threads.unwrap_or_else(|| (available_cpus() / 2).max(1)))
+        .build()
+        .map_err(io::Error::other)?;
     let mut all_files = Vec::new();
-    collect_files(Path::new(directory), file_extension, args.recursive, &mut all_files)?;
+    let (excluded, included): (Vec<&GlobFilter>, Vec<&GlobFilter>) = args.glob.iter().partition(|filter| filter.negated);
+    let include: Vec<Glob> = included.into_iter().map(|filter| filter.glob.clone()).collect();
+    let exclude: Vec<Glob> = args
+        .glob_not
+        .iter()
+        .cloned()
+        .chain(excluded.into_iter().map(|filter| filter.glob.clone()))
+        .chain(args.type_not_globs.iter().cloned())
+        .collect();
+    let walk_options = WalkOptions {
+        extensions: file_extensions,
+        recursive: args.recursive,
+        follow: args.follow,
+        max_depth: args.max_depth,
+        include_vendored: args.include_vendored,
+        include: &include,
+        types: &args.type_globs,
+        exclude: &exclude,
+        strict: args.strict_walk,
+        ignore: !args.no_ignore,
+        hidden: args.hidden,
+    };
+    let skipped = walk_pool.install(|| walk::collect_files(Path::new(directory), &walk_options, &mut all_files))?;
+    for (path, e) in skipped {
+        let warning = i18n::tr("walk-skipped", &[("path", &path.display()), ("detail", &e)]);
+        eprintln!("{}", warning.themed(theme::palette().notice));
+    }
+    walk::dedup_canonical(&mut all_files);
+    if let Some(changed) = &changed {
+        all_files.retain(|path| changed.lines(path).is_some());
+    }
+    walk_pool.install(|| walk::schedule(&mut all_files, args.walk_order));
+    let mut links = walk_pool.install(|| walk::dedup_hard_links(&mut all_files));
+    profiler.record_walk(walk_start.elapsed());
+    if let Some(style) = args.path_style {
+        for path in all_files.iter_mut().chain(links.values_mut().flatten()) {
+            *path = paths::format_path(path, style);
+        }
+    }
 
     let pb = ProgressBar::new(all_files.len() as u64);
     pb.set_style(ProgressStyle::default_bar()
@@ -85,168 +968,398 @@ fn search_files(
         .unwrap()
         .progress_chars("#>-"));
 
-    let results: Vec<SearchResult> = all_files.par_iter()
-        .filter_map(|path| {
-            let result = search_in_file(path, pattern, args);
-            pb.inc(1);
-            result.ok()
-        })
-        .flatten()
-        .collect();
-
-    pb.finish_with_message("Search completed");
-    Ok(results)
-}
-
-fn should_search_file(path: &Path, extension: &Option<String>) -> bool {
-    if let Some(ext) = extension {
-        if let Some(file_ext) = path.extension() {
-            return file_ext.to_string_lossy().to_string() == *ext;
-        }
-        false
-    } else {
-        path.is_file()
-    }
-}
+    // Searchers send each file's results through a bounded channel, so a slow
+    // consumer (terminal, pager) blocks them instead of letting results pile
+    // up. Batches are reordered by file index to keep output deterministic,
+    // except for JSON Lines, which trades that for printing results sooner.
+    let (tx, rx) = mpsc::sync_channel::<(usize, Vec<SearchResult>)>(RESULT_CHANNEL_CAPACITY);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            all_files.par_iter().enumerate().for_each_with(tx, |tx, (index, path)| {
+                let mut file_profile = FileProfile { path: path.clone(), ..Default::default() };
+                let result = search_in_file(path, pattern, args, profiler, &mut file_profile);
+                profiler.record_file(file_profile);
+                pb.inc(1);
+                // Files that couldn't be read or were skipped as binary weren't
+                // searched, so --files-without-match doesn't list them.
+                let (mut results, searched) = match result {
+                    Ok(results) => (results, true),
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                        pb.suspend(|| eprintln!("{}", i18n::error(e).red()));
+                        (Vec::new(), false)
+                    }
+                    Err(_) => (Vec::new(), false),
+                };
+                if args.all_of == Some(AllOf::File) {
+                    if !pattern.covers_all(&results.iter().flat_map(|r| pattern.terms_in(&r.line)).collect::<Vec<_>>()) {
+                        results.clear();
+                    } else if args.first_match_only() {
+                        results.truncate(1);
+                    }
+                }
+                if !args.near.is_empty() {
+                    keep_near(&mut results, pattern, args.within);
+                    if args.first_match_only() {
+                        results.truncate(1);
+                    }
+                }
+                if let Some(lines) = changed.as_ref().and_then(|changed| changed.lines(path)) {
+                    results.retain(|r| lines.iter().any(|range| range.contains(&r.line_number)));
+                }
+                if args.tests_only || args.no_tests {
+                    testcode::retain(path, &args.directory, &mut results, args.tests_only);
+                }
+                if args.resolve_sourcemaps {
+                    sourcemap::resolve(path, &mut results);
+                }
+                // For --files-without-match, a file is listed through a
+                // single result with no matches when it had none.
+                if args.files_without_match {
+                    results = match searched && results.is_empty() {
+                        true => vec![SearchResult {
+                            file_path: path.clone(),
+                            line_number: 0,
+                            line: String::new(),
+                            matches: Vec::new(),
+                            byte_offset: None,
+                            terms: Vec::new(),
+                            origin: None,
+                            cell: None,
+                            context: Vec::new(),
+                        }],
+                        false => Vec::new(),
+                    };
+                }
+                if let Some(others) = links.get(&index).filter(|_| args.all_links) {
+                    let found = results.len();
+                    for other in others {
+                        for i in 0..found {
+                            let copy = SearchResult { file_path: other.clone(), ..results[i].clone() };
+                            results.push(copy);
+                        }
+                    }
+                }
+                // The receiver only goes away if the consumer panicked.
+                let _ = tx.send((index, results));
+            });
+        });
 
-fn collect_files(
-    dir: &Path,
-    extension: &Option<String>,
-    recursive: bool,
-    files: &mut Vec<String>,
-) -> io::Result<()> {
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() && should_search_file(&path, extension) {
-                if let Some(path_str) = path.to_str() {
-                    files.push(path_str.to_string());
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, results) in rx {
+            if args.format == Format::Jsonl {
+                if !results.is_empty() {
+                    pb.suspend(|| sink(results));
+                }
+                continue;
+            }
+            pending.insert(index, results);
+            while let Some(results) = pending.remove(&next) {
+                next += 1;
+                if !results.is_empty() {
+                    pb.suspend(|| sink(results));
                 }
-            } else if recursive && path.is_dir() {
-                collect_files(&path, extension, recursive, files)?;
             }
         }
-    }
+    });
+
+    pb.finish_with_message("Search completed");
     Ok(())
 }
 
 fn search_in_file(
     file_path: &str,
-    pattern: &Regex,
+    pattern: &Matcher,
     args: &Args,
+    profiler: &Profiler,
+    file_profile: &mut FileProfile,
 ) -> io::Result<Vec<SearchResult>> {
-    let file = fs::File::open(file_path)?;
-    let metadata = file.metadata()?;
+    if !args.include_generated && heuristics::is_generated_name(file_path) {
+        return Ok(Vec::new());
+    }
+    if (args.tests_only || args.no_tests) && testcode::skips_file(file_path, &args.directory, args.tests_only) {
+        return Ok(Vec::new());
+    }
+    let opened = profiler.time(&mut file_profile.open, || -> io::Result<_> {
+        let mut file = fs::File::open(file_path)?;
+        let metadata = file.metadata()?;
+        if !args.include_generated && heuristics::has_generated_marker(&mut file)? {
+            return Ok(None);
+        }
+        if !(args.search_minified || args.resolve_sourcemaps) && heuristics::is_minified(file_path, &mut file, metadata.len())? {
+            return Ok(None);
+        }
+        Ok(Some((file, metadata)))
+    })?;
+    let Some((mut file, metadata)) = opened else { return Ok(Vec::new()) };
+
+    if !args.text && binary::is_binary(&mut file)? {
+        // Binary files have no lines to invert.
+        if args.invert_match || !(args.binary || args.hex_context.is_some()) {
+            return Err(io::Error::other(binary::Skipped));
+        }
+        return profiler.time(&mut file_profile.search, || binary::search(&file, file_path, pattern, args));
+    }
+
+    if let Some(selector) = &args.select {
+        let mut content = String::new();
+        profiler.time(&mut file_profile.read, || file.read_to_string(&mut content))?;
+        let mut results = profiler.time(&mut file_profile.search, || markup::search(&content, file_path, selector, pattern));
+        context::attach_content(&mut results, &content, args);
+        return Ok(results);
+    }
+
+    if args.imports {
+        let Some(language) = imports::language(file_path) else { return Ok(Vec::new()) };
+        let mut content = String::new();
+        profiler.time(&mut file_profile.read, || file.read_to_string(&mut content))?;
+        let mut results = profiler.time(&mut file_profile.search, || imports::search(&content, file_path, language, pattern, args));
+        context::attach_content(&mut results, &content, args);
+        return Ok(results);
+    }
+
+    if args.symbols {
+        let Some(definitions) = symbols::definitions(file_path) else { return Ok(Vec::new()) };
+        let mut content = String::new();
+        profiler.time(&mut file_profile.read, || file.read_to_string(&mut content))?;
+        let mut results = profiler.time(&mut file_profile.search, || {
+            symbols::search(&content, file_path, definitions, pattern, args)
+        });
+        context::attach_content(&mut results, &content, args);
+        return Ok(results);
+    }
+
+    if notebook::is_notebook(file_path) {
+        let mut content = String::new();
+        profiler.time(&mut file_profile.read, || file.read_to_string(&mut content))?;
+        return profiler.time(&mut file_profile.search, || notebook::search(&content, file_path, pattern, args));
+    }
+
+    if args.scope.is_some() && highlight::detect(file_path).is_none() {
+        return Ok(Vec::new());
+    }
 
     if metadata.len() > LARGE_FILE_THRESHOLD {
-        search_in_large_file(file_path, pattern, args)
+        search_in_large_file(file, file_path, pattern, args, profiler, file_profile)
     } else {
-        search_in_small_file(file, file_path, pattern, args)
+        search_in_small_file(file, file_path, pattern, args, profiler, file_profile)
     }
 }
 
 fn search_in_small_file(
     file: fs::File,
     file_path: &str,
-    pattern: &Regex,
+    pattern: &Matcher,
     args: &Args,
+    profiler: &Profiler,
+    file_profile: &mut FileProfile,
 ) -> io::Result<Vec<SearchResult>> {
-    let reader = io::BufReader::new(file);
+    let mut reader = BufReader::new(file);
     let mut results = Vec::new();
+    let mut buf = Vec::new();
+    let mut offset = 0;
 
-    for (line_number, line) in reader.lines().enumerate() {
-        let line = line?;
-        let line_to_search = if args.case_sensitive {
-            line.clone()
-        } else {
-            line.to_lowercase()
+    match encoding::sniff_bom(reader.fill_buf()?) {
+        Some(bom @ (Bom::Utf16Le | Bom::Utf16Be)) => {
+            let content = profiler.time(&mut file_profile.read, || -> io::Result<_> {
+                reader.read_to_end(&mut buf)?;
+                encoding::decode_utf16(&buf[bom.len()..], bom, file_path, args.encoding_errors)
+            })?;
+            return Ok(match content {
+                Some(content) => profiler.time(&mut file_profile.search, || {
+                    search_content(&content, file_path, pattern, args)
+                }),
+                None => Vec::new(),
+            });
+        }
+        Some(Bom::Utf8) => {
+            reader.consume(Bom::Utf8.len());
+            offset = Bom::Utf8.len();
+        }
+        None => {}
+    }
+
+    let whole_file_listed = args.all_of == Some(AllOf::File) && args.first_match_only();
+    let mut seen = pattern.term_set();
+    let mut sections = (!args.section.is_empty()).then(|| Sections::new(&args.section));
+    let mut scope = args.scope.and_then(|scope| Some((highlight::detect(file_path)?, highlight::State::default(), scope)));
+    // Context lines come from the decoded lines as they are read. Once the
+    // search stops at a match, reading goes on as far as its context reaches.
+    let keep_lines = context::wanted(args);
+    let mut lines = Vec::new();
+    let mut stopped_at = None;
+    for line_number in 0.. {
+        if stopped_at.is_some_and(|stopped| context::reach_after(args).is_some_and(|after| line_number > stopped + after)) {
+            break;
+        }
+        let decoded = profiler.time(&mut file_profile.read, || -> io::Result<_> {
+            buf.clear();
+            let read = reader.read_until(b'\n', &mut buf)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            let line = encoding::decode(trim_line_ending(&buf), offset, file_path, args.encoding_errors)?;
+            Ok(Some((read, line.map(Cow::into_owned))))
+        })?;
+        let mut line = match decoded {
+            None => break,
+            Some((_, None)) => return Ok(Vec::new()),
+            Some((read, Some(line))) => {
+                offset += read;
+                line
+            }
         };
+        match limits::clip(&line, args.max_line_length, args.long_lines) {
+            None => return Ok(Vec::new()),
+            Some(clipped) => {
+                let len = clipped.len();
+                line.truncate(len);
+            }
+        }
+        if keep_lines {
+            lines.push(line.clone());
+        }
+        if stopped_at.is_some() {
+            continue;
+        }
+        if sections.as_mut().is_some_and(|sections| !sections.contains(&line)) {
+            continue;
+        }
 
-        let matches: Vec<_> = pattern.find_iter(&line_to_search)
-            .map(|m| (m.start(), m.end()))
-            .collect();
+        let (mut matches, mut terms) = profiler.time(&mut file_profile.search, || {
+            pattern.find(&line)
+        });
+        if let Some((syntax, state, scope)) = scope.as_mut() {
+            let ranges = syntax.scope_ranges(&line, state, *scope);
+            highlight::retain_in_scope(&ranges, &mut matches, &mut terms);
+        }
+        if whole_file_listed && !matches.is_empty() {
+            pattern.terms_in(&line).into_iter().for_each(|term| seen.insert(term));
+        }
 
-        if !matches.is_empty() {
+        if matches.is_empty() == args.invert_match {
+            let (matches, terms) = if args.invert_match { Default::default() } else { (matches, terms) };
             results.push(SearchResult {
                 file_path: file_path.to_string(),
                 line_number: line_number + 1,
                 line,
                 matches,
+                byte_offset: None,
+                terms,
+                origin: None,
+                cell: None,
+                context: Vec::new(),
             });
+            if args.stop_at_first_match() {
+                if !keep_lines {
+                    break;
+                }
+                stopped_at = Some(line_number);
+            }
+        }
+        // For `--all-of file -l`, the file is settled once every term has
+        // turned up.
+        if whole_file_listed && seen.is_full() {
+            break;
         }
     }
 
+    context::attach(&mut results, &lines, args);
     Ok(results)
 }
 
 fn search_in_large_file(
+    file: fs::File,
     file_path: &str,
-    pattern: &Regex,
+    pattern: &Matcher,
     args: &Args,
+    profiler: &Profiler,
+    file_profile: &mut FileProfile,
 ) -> io::Result<Vec<SearchResult>> {
-    let file = fs::File::open(file_path)?;
     let mmap = unsafe { Mmap::map(&file)? };
 
-    let content = String::from_utf8_lossy(&mmap);
-    let lines: Vec<&str> = content.lines().collect();
-
-    let results: Vec<SearchResult> = lines.par_iter()
-        .enumerate()
-        .filter_map(|(line_number, &line)| {
-            let line_to_search = if args.case_sensitive {
-                line.to_string()
-            } else {
-                line.to_lowercase()
-            };
-
-            let matches: Vec<_> = pattern.find_iter(&line_to_search)
-                .map(|m| (m.start(), m.end()))
-                .collect();
-
-            if matches.is_empty() {
-                None
-            } else {
-                Some(SearchResult {
-                    file_path: file_path.to_string(),
-                    line_number: line_number + 1,
-                    line: line.to_string(),
-                    matches,
-                })
+    let content = profiler.time(&mut file_profile.read, || -> io::Result<_> {
+        Ok(match encoding::sniff_bom(&mmap) {
+            Some(bom @ (Bom::Utf16Le | Bom::Utf16Be)) => {
+                encoding::decode_utf16(&mmap[bom.len()..], bom, file_path, args.encoding_errors)?.map(Cow::Owned)
             }
+            Some(Bom::Utf8) => {
+                let len = Bom::Utf8.len();
+                encoding::decode(&mmap[len..], len, file_path, args.encoding_errors)?
+            }
+            None => encoding::decode(&mmap, 0, file_path, args.encoding_errors)?,
         })
-        .collect();
+    })?;
 
-    Ok(results)
+    Ok(match content {
+        Some(content) => profiler.time(&mut file_profile.search, || {
+            search_content(&content, file_path, pattern, args)
+        }),
+        None => Vec::new(),
+    })
 }
 
-fn display_results(results: Vec<SearchResult>) {
-    if results.is_empty() {
-        println!("{}", "No matches found.".yellow());
-        return;
-    }
-
-    println!("\n{} matches found:\n", results.len().to_string().green());
+/// Searches already-decoded file content, splitting lines across the pool.
+fn search_content(
+    content: &str,
+    file_path: &str,
+    pattern: &Matcher,
+    args: &Args,
+) -> Vec<SearchResult> {
+    let lines: Option<Vec<&str>> = content
+        .lines()
+        .map(|line| limits::clip(line, args.max_line_length, args.long_lines))
+        .collect();
+    let Some(lines) = lines else { return Vec::new() };
+    let in_section: Option<Vec<bool>> = (!args.section.is_empty()).then(|| {
+        let mut sections = Sections::new(&args.section);
+        lines.iter().map(|line| sections.contains(line)).collect()
+    });
+    // Block comments carry over between lines, so scopes are worked out in
+    // order before the lines are searched in parallel.
+    let scopes: Option<Vec<Vec<(usize, usize)>>> = args.scope.and_then(|scope| {
+        let syntax = highlight::detect(file_path)?;
+        let mut state = highlight::State::default();
+        Some(lines.iter().map(|line| syntax.scope_ranges(line, &mut state, scope)).collect())
+    });
 
-    for result in results {
-        println!("{}:{} {}",
-                 result.file_path.blue(),
-                 result.line_number.to_string().yellow(),
-                 highlight_matches(&result.line, &result.matches)
-        );
-    }
-}
+    let search_line = |(line_number, &line): (usize, &&str)| {
+        if in_section.as_ref().is_some_and(|in_section| !in_section[line_number]) {
+            return None;
+        }
+        let (mut matches, mut terms) = pattern.find(line);
+        if let Some(scopes) = &scopes {
+            highlight::retain_in_scope(&scopes[line_number], &mut matches, &mut terms);
+        }
 
-fn highlight_matches(line: &str, matches: &[(usize, usize)]) -> String {
-    let mut result = String::new();
-    let mut last_end = 0;
+        if matches.is_empty() == args.invert_match {
+            let (matches, terms) = if args.invert_match { Default::default() } else { (matches, terms) };
+            Some(SearchResult {
+                file_path: file_path.to_string(),
+                line_number: line_number + 1,
+                line: line.to_string(),
+                matches,
+                byte_offset: None,
+                terms,
+                origin: None,
+                cell: None,
+                context: Vec::new(),
+            })
+        } else {
+            None
+        }
+    };
 
-    for &(start, end) in matches {
-        result.push_str(&line[last_end..start]);
-        result.push_str(&line[start..end].on_yellow().to_string());
-        last_end = end;
-    }
-    result.push_str(&line[last_end..]);
+    let mut results: Vec<SearchResult> = if args.stop_at_first_match() {
+        lines.par_iter().enumerate().find_map_first(search_line).into_iter().collect()
+    } else {
+        lines.par_iter().enumerate().filter_map(search_line).collect()
+    };
+    context::attach(&mut results, &lines, args);
+    results
+}
 
-    result
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
 }
@@ -0,0 +1,337 @@
+use crate::pattern::Matcher;
+use crate::SearchResult;
+
+/// HTML elements that never have content or a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+/// HTML elements whose content is raw text rather than markup.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// A `--select` selector: CSS-like steps such as `div.note > p`, optionally
+/// ending in `@attr` to search that attribute's value instead of text.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    steps: Vec<Step>,
+    attribute: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    /// Whether this step must be a direct child of the previous one, rather
+    /// than any descendant.
+    child: bool,
+    /// Element name, or `None` for `*`.
+    name: Option<String>,
+    classes: Vec<String>,
+    id: Option<String>,
+}
+
+/// Parses a selector: steps separated by spaces (descendant) or `>`
+/// (child), each a name or `*` followed by any `.class` and `#id`, with an
+/// optional trailing `@attr`.
+pub fn parse_selector(spec: &str) -> Result<Selector, String> {
+    let (steps_spec, attribute) = match spec.rsplit_once('@') {
+        Some((steps, attr)) if !attr.trim().is_empty() => (steps, Some(attr.trim().to_string())),
+        Some(_) => return Err(format!("missing attribute name in '{}'", spec)),
+        None => (spec, None),
+    };
+    let mut steps = Vec::new();
+    let mut child = false;
+    for token in steps_spec.replace('>', " > ").split_whitespace() {
+        if token == ">" {
+            if steps.is_empty() || child {
+                return Err(format!("misplaced '>' in '{}'", spec));
+            }
+            child = true;
+            continue;
+        }
+        steps.push(parse_step(token, child).map_err(|e| format!("{} in '{}'", e, spec))?);
+        child = false;
+    }
+    if child {
+        return Err(format!("'>' needs an element after it in '{}'", spec));
+    }
+    if steps.is_empty() {
+        // A bare `@attr` selects that attribute on any element.
+        steps.push(Step { child: false, name: None, classes: Vec::new(), id: None });
+    }
+    Ok(Selector { steps, attribute })
+}
+
+fn parse_step(token: &str, child: bool) -> Result<Step, String> {
+    let mut step = Step { child, name: None, classes: Vec::new(), id: None };
+    let name_end = token.find(['.', '#']).unwrap_or(token.len());
+    match &token[..name_end] {
+        "" | "*" => {}
+        name => step.name = Some(name.to_string()),
+    }
+    let mut rest = &token[name_end..];
+    while let Some(kind) = rest.chars().next() {
+        let end = rest[1..].find(['.', '#']).map_or(rest.len(), |i| i + 1);
+        let value = &rest[1..end];
+        if value.is_empty() {
+            return Err(format!("empty '{}'", kind));
+        }
+        match kind {
+            '.' => step.classes.push(value.to_string()),
+            _ => step.id = Some(value.to_string()),
+        }
+        rest = &rest[end..];
+    }
+    Ok(step)
+}
+
+/// An open element while scanning.
+struct Element {
+    name: String,
+    classes: Vec<String>,
+    id: Option<String>,
+    /// Whether text in here is selected: this element or an ancestor
+    /// matches the selector.
+    selected: bool,
+}
+
+impl Step {
+    fn matches(&self, element: &Element) -> bool {
+        self.name.as_ref().is_none_or(|name| name.eq_ignore_ascii_case(&element.name))
+            && self.id.as_ref().is_none_or(|id| element.id.as_ref() == Some(id))
+            && self.classes.iter().all(|class| element.classes.contains(class))
+    }
+}
+
+impl Selector {
+    /// Whether the innermost element of `stack` matches, checking steps
+    /// right to left as CSS does.
+    fn matches(&self, stack: &[Element]) -> bool {
+        fn matches_at(steps: &[Step], stack: &[Element]) -> bool {
+            let (Some((step, before)), Some(element)) = (steps.split_last(), stack.last()) else { return false };
+            if !step.matches(element) {
+                return false;
+            }
+            let parents = &stack[..stack.len() - 1];
+            match before.is_empty() {
+                true => true,
+                false if step.child => matches_at(before, parents),
+                false => (1..=parents.len()).rev().any(|len| matches_at(before, &parents[..len])),
+            }
+        }
+        matches_at(&self.steps, stack)
+    }
+}
+
+/// A stretch of the file to search, with the element path it belongs to.
+struct Region {
+    start: usize,
+    end: usize,
+    path: String,
+}
+
+/// Searches the text (or `@attr` values) of the elements `selector`
+/// selects in the XML/HTML `content`. Reported lines are whole lines of the
+/// file, with only the matches inside selected regions, and carry the
+/// element path as their origin. Entities are searched as written.
+pub fn search(content: &str, file_path: &str, selector: &Selector, pattern: &Matcher) -> Vec<SearchResult> {
+    let line_starts: Vec<usize> =
+        std::iter::once(0).chain(content.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let mut results: Vec<SearchResult> = Vec::new();
+    for region in regions(content, selector) {
+        let mut start = region.start;
+        while start < region.end {
+            let line_index = line_starts.partition_point(|&line_start| line_start <= start) - 1;
+            let line_start = line_starts[line_index];
+            let line_end = line_starts.get(line_index + 1).map_or(content.len(), |&next| next - 1);
+            let end = region.end.min(line_end);
+            let (spans, terms) = pattern.find(&content[start..end]);
+            if !spans.is_empty() {
+                let shift = start - line_start;
+                let spans = spans.into_iter().map(|(s, e)| (s + shift, e + shift));
+                let line_number = line_index + 1;
+                match results.last_mut() {
+                    Some(last) if last.line_number == line_number && last.origin.as_ref() == Some(&region.path) => {
+                        last.matches.extend(spans);
+                        last.terms.extend(terms);
+                    }
+                    _ => results.push(SearchResult {
+                        file_path: file_path.to_string(),
+                        line_number,
+                        line: content[line_start..line_end].trim_end_matches('\r').to_string(),
+                        matches: spans.collect(),
+                        byte_offset: None,
+                        terms,
+                        origin: Some(region.path.clone()),
+                        cell: None,
+                        context: Vec::new(),
+                    }),
+                }
+            }
+            start = line_end + 1;
+        }
+    }
+    results
+}
+
+/// Scans `content` for the text or attribute values the selector selects.
+/// The scan is forgiving: unclosed elements stay open, and stray closing
+/// tags close back to the nearest matching open element or are ignored.
+fn regions(content: &str, selector: &Selector) -> Vec<Region> {
+    let mut regions = Vec::new();
+    let mut stack: Vec<Element> = Vec::new();
+    let path = |stack: &[Element]| stack.iter().map(|e| e.name.as_str()).collect::<Vec<_>>().join(" > ");
+    let mut pos = 0;
+
+    while pos < content.len() {
+        let rest = &content[pos..];
+        if !rest.starts_with('<') {
+            let end = rest.find('<').map_or(content.len(), |i| pos + i);
+            if selector.attribute.is_none() && stack.last().is_some_and(|e| e.selected) {
+                regions.push(Region { start: pos, end, path: path(&stack) });
+            }
+            pos = end;
+            continue;
+        }
+        if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let len = body.find("]]>").unwrap_or(body.len());
+            let start = pos + "<![CDATA[".len();
+            if selector.attribute.is_none() && stack.last().is_some_and(|e| e.selected) {
+                regions.push(Region { start, end: start + len, path: path(&stack) });
+            }
+            pos = (start + len + "]]>".len()).min(content.len());
+            continue;
+        }
+        let skip_to = |close: &str| rest.find(close).map_or(content.len(), |i| pos + i + close.len());
+        if rest.starts_with("<!--") {
+            pos = skip_to("-->");
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            pos = skip_to(">");
+            continue;
+        }
+        if let Some(closing) = rest.strip_prefix("</") {
+            let name_len = closing.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(closing.len());
+            let name = &closing[..name_len];
+            if let Some(open) = stack.iter().rposition(|e| e.name.eq_ignore_ascii_case(name)) {
+                stack.truncate(open);
+            }
+            pos = skip_to(">");
+            continue;
+        }
+
+        let Some(tag) = parse_tag(content, pos) else {
+            // A `<` that doesn't start a tag is text.
+            let end = rest[1..].find('<').map_or(content.len(), |i| pos + 1 + i);
+            if selector.attribute.is_none() && stack.last().is_some_and(|e| e.selected) {
+                regions.push(Region { start: pos, end, path: path(&stack) });
+            }
+            pos = end;
+            continue;
+        };
+        let attribute = |name: &str| tag.attributes.iter().find(|(key, _)| key.eq_ignore_ascii_case(name));
+        let value = |name: &str| attribute(name).map(|&(_, (start, end))| content[start..end].to_string());
+        let element = Element {
+            classes: value("class").map(|v| v.split_whitespace().map(str::to_string).collect()).unwrap_or_default(),
+            id: value("id"),
+            name: tag.name.to_string(),
+            selected: false,
+        };
+        stack.push(element);
+        let matched = selector.matches(&stack);
+        let parent_selected = stack.len() > 1 && stack[stack.len() - 2].selected;
+        if let Some(last) = stack.last_mut() {
+            last.selected = matched || parent_selected;
+        }
+        if let Some(name) = selector.attribute.as_deref().filter(|_| matched) {
+            if let Some(&(_, (start, end))) = attribute(name) {
+                regions.push(Region { start, end, path: format!("{}@{}", path(&stack), name) });
+            }
+        }
+        pos = tag.end;
+
+        let lower = tag.name.to_ascii_lowercase();
+        if tag.self_closing || VOID_ELEMENTS.contains(&lower.as_str()) {
+            stack.pop();
+        } else if RAW_TEXT_ELEMENTS.contains(&lower.as_str()) {
+            let close = format!("</{}", lower);
+            let end = content[pos..].to_ascii_lowercase().find(&close).map_or(content.len(), |i| pos + i);
+            if selector.attribute.is_none() && stack.last().is_some_and(|e| e.selected) {
+                regions.push(Region { start: pos, end, path: path(&stack) });
+            }
+            pos = end;
+        }
+    }
+    regions
+}
+
+/// An opening tag: its name, attributes as name and value span, where it
+/// ends and whether it closes itself (`<br/>`).
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, (usize, usize))>,
+    end: usize,
+    self_closing: bool,
+}
+
+fn parse_tag(content: &str, start: usize) -> Option<Tag<'_>> {
+    let bytes = content.as_bytes();
+    let is_name = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b':' | b'.') || b >= 0x80;
+    let mut pos = start + 1;
+    let name_start = pos;
+    while pos < bytes.len() && is_name(bytes[pos]) {
+        pos += 1;
+    }
+    if pos == name_start || !bytes[name_start].is_ascii_alphabetic() && bytes[name_start] < 0x80 {
+        return None;
+    }
+    let name = &content[name_start..pos];
+    let mut attributes = Vec::new();
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        match bytes.get(pos) {
+            None => return Some(Tag { name, attributes, end: content.len(), self_closing: false }),
+            Some(b'>') => return Some(Tag { name, attributes, end: pos + 1, self_closing: false }),
+            Some(b'/') if bytes.get(pos + 1) == Some(&b'>') => {
+                return Some(Tag { name, attributes, end: pos + 2, self_closing: true });
+            }
+            Some(_) => {}
+        }
+        let key_start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && !matches!(bytes[pos], b'=' | b'>' | b'/') {
+            pos += 1;
+        }
+        if pos == key_start {
+            pos += 1; // a stray `/` or `=`
+            continue;
+        }
+        let key = &content[key_start..pos];
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if bytes.get(pos) != Some(&b'=') {
+            attributes.push((key, (pos, pos)));
+            continue;
+        }
+        pos += 1;
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let value = match bytes.get(pos) {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let value_start = pos + 1;
+                let value_end = content[value_start..].find(quote as char).map_or(content.len(), |i| value_start + i);
+                pos = (value_end + 1).min(content.len());
+                (value_start, value_end)
+            }
+            _ => {
+                let value_start = pos;
+                while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' {
+                    pos += 1;
+                }
+                (value_start, pos)
+            }
+        };
+        attributes.push((key, value));
+    }
+}
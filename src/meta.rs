@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::UNIX_EPOCH;
+
+use clap::ValueEnum;
+
+/// A file attribute that can be shown next to each result.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum MetaField {
+    /// File size in bytes
+    Size,
+    /// Last modification time (UTC)
+    Mtime,
+    /// Owning user (Unix only)
+    Owner,
+}
+
+impl MetaField {
+    pub fn name(self) -> &'static str {
+        match self {
+            MetaField::Size => "size",
+            MetaField::Mtime => "mtime",
+            MetaField::Owner => "owner",
+        }
+    }
+
+    /// Formats this attribute of `meta`, or `None` if it is unavailable.
+    pub fn value(self, meta: &fs::Metadata) -> Option<String> {
+        match self {
+            MetaField::Size => Some(meta.len().to_string()),
+            MetaField::Mtime => meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| format_timestamp(d.as_secs())),
+            MetaField::Owner => owner(meta),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn owner(meta: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    use std::sync::OnceLock;
+
+    static USERS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    let users = USERS.get_or_init(|| {
+        fs::read_to_string("/etc/passwd")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let uid = fields.nth(1)?.parse().ok()?;
+                Some((uid, name.to_string()))
+            })
+            .collect()
+    });
+
+    let uid = meta.uid();
+    Some(users.get(&uid).cloned().unwrap_or_else(|| uid.to_string()))
+}
+
+#[cfg(not(unix))]
+fn owner(_meta: &fs::Metadata) -> Option<String> {
+    None
+}
+
+/// Looks up file metadata once per path while results are printed.
+#[derive(Default)]
+pub struct MetaCache {
+    entries: HashMap<String, Option<fs::Metadata>>,
+}
+
+impl MetaCache {
+    pub fn get(&mut self, path: &str) -> Option<&fs::Metadata> {
+        self.entries
+            .entry(path.to_string())
+            .or_insert_with(|| fs::metadata(path).ok())
+            .as_ref()
+    }
+}
+
+/// Renders `fields` of `meta` as `name=value` pairs separated by spaces.
+pub fn describe(meta: Option<&fs::Metadata>, fields: &[MetaField]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            let value = meta.and_then(|m| field.value(m)).unwrap_or_else(|| "-".to_string());
+            format!("{}={}", field.name(), value)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+// Howard Hinnant's days-to-civil algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_are_rfc3339_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1_709_164_800), "2024-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(951_914_096), "2000-03-01T12:34:56Z");
+    }
+
+    #[test]
+    fn describe_names_each_field() {
+        let path = std::env::temp_dir().join(format!("fsearch-test-{}-meta", std::process::id()));
+        fs::write(&path, "12345").unwrap();
+        let meta = fs::metadata(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(describe(Some(&meta), &[MetaField::Size]), "size=5");
+        let mtime = describe(Some(&meta), &[MetaField::Mtime]);
+        assert!(mtime.starts_with("mtime=") && mtime.ends_with('Z'), "{}", mtime);
+        assert_eq!(describe(None, &[MetaField::Size, MetaField::Owner]), "size=- owner=-");
+    }
+}
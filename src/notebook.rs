@@ -0,0 +1,119 @@
+use std::io;
+
+use crate::context;
+use crate::json::{self, Value};
+use crate::pattern::Matcher;
+use crate::{Args, SearchResult};
+
+/// Whether `path` is a Jupyter notebook, searched cell by cell.
+pub fn is_notebook(path: &str) -> bool {
+    path.rsplit_once('.').is_some_and(|(_, ext)| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Searches the cell sources of a notebook (and the text of its outputs,
+/// unless `--no-notebook-outputs`) instead of its raw JSON. Results carry
+/// the number of their cell, count lines within the cell's source or output
+/// and have that text as their origin; context lines come from it too.
+pub fn search(content: &str, file_path: &str, pattern: &Matcher, args: &Args) -> io::Result<Vec<SearchResult>> {
+    let notebook = json::parse(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: not a valid notebook: {}", file_path, e)))?;
+    let cells = match notebook.get("cells") {
+        Some(Value::Array(cells)) => cells.as_slice(),
+        _ => &[],
+    };
+
+    let mut results = Vec::new();
+    for (index, cell) in cells.iter().enumerate() {
+        let kind = match cell.get("cell_type") {
+            Some(Value::Str(kind)) => kind.as_str(),
+            _ => "cell",
+        };
+        let mut texts = vec![(format!("{} cell {}", kind, index + 1), cell.get("source"))];
+        if !args.no_notebook_outputs {
+            let outputs = match cell.get("outputs") {
+                Some(Value::Array(outputs)) => outputs.as_slice(),
+                _ => &[],
+            };
+            for output in outputs {
+                let text = output.get("text").or_else(|| output.get("data").and_then(|data| data.get("text/plain")));
+                texts.push((format!("output of cell {}", index + 1), text));
+            }
+        }
+        for (label, text) in texts {
+            let lines = lines(text);
+            let mut found = Vec::new();
+            for (number, line) in lines.iter().enumerate() {
+                let (matches, terms) = pattern.find(line);
+                if matches.is_empty() != args.invert_match {
+                    continue;
+                }
+                let (matches, terms) = if args.invert_match { Default::default() } else { (matches, terms) };
+                found.push(SearchResult {
+                    file_path: file_path.to_string(),
+                    line_number: number + 1,
+                    line: line.clone(),
+                    matches,
+                    byte_offset: None,
+                    terms,
+                    origin: Some(label.clone()),
+                    cell: Some(index + 1),
+                    context: Vec::new(),
+                });
+                if args.stop_at_first_match() {
+                    break;
+                }
+            }
+            context::attach(&mut found, &lines, args);
+            results.append(&mut found);
+            if args.stop_at_first_match() && !results.is_empty() {
+                return Ok(results);
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// The lines of a cell source or output text, which notebooks store as one
+/// string or as a list of strings that each end with their newline.
+fn lines(text: Option<&Value>) -> Vec<String> {
+    let pieces: Vec<&str> = match text {
+        Some(Value::Str(text)) => vec![text],
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| match item {
+                Value::Str(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    pieces.concat().lines().map(String::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn found(notebook: &str, flags: &[&str]) -> Vec<(usize, Option<usize>, String)> {
+        let args = Args::parse_from(["fsearch", "-t", "foo"].iter().chain(flags));
+        let pattern = crate::pattern::compile(&args).unwrap();
+        let results = search(notebook, "n.ipynb", &pattern, &args).unwrap();
+        results.into_iter().map(|r| (r.line_number, r.cell, r.line)).collect()
+    }
+
+    #[test]
+    fn counts_lines_within_string_sources() {
+        let notebook = r#"{"cells": [{"cell_type": "code", "source": "a = 1\nfoo(a)\nb = foo"}]}"#;
+        assert_eq!(found(notebook, &[]), vec![(2, Some(1), "foo(a)".to_string()), (3, Some(1), "b = foo".to_string())]);
+    }
+
+    #[test]
+    fn joins_list_sources_and_skips_outputs_on_request() {
+        let notebook = r#"{"cells": [
+            {"cell_type": "markdown", "source": []},
+            {"cell_type": "code", "source": ["x = f", "oo()\n", "foo\n"], "outputs": [{"text": "foo\n"}]}
+        ]}"#;
+        assert_eq!(found(notebook, &["--no-notebook-outputs"]), vec![(1, Some(2), "x = foo()".to_string()), (2, Some(2), "foo".to_string())]);
+        assert_eq!(found(notebook, &[]).len(), 3);
+    }
+}
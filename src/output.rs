@@ -0,0 +1,653 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Component, Path};
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use colored::Colorize;
+use console::{Key, Term};
+
+use crate::binary;
+use crate::context;
+use crate::csv::{self, Dialect};
+use crate::i18n;
+use crate::highlight::{self, State};
+use crate::json;
+use crate::meta::{self, MetaCache};
+use crate::sarif;
+use crate::sink::Sinks;
+use crate::template::Template;
+use crate::text;
+use crate::theme::{self, Themed};
+use crate::{Args, SearchResult};
+
+/// How results are written.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Colored lines for people (--template and --fields apply here)
+    Text,
+    /// A JSON array with one object per matching line
+    Json,
+    /// One JSON object per match and line, printed as soon as each file has
+    /// been searched (so files may appear out of order)
+    Jsonl,
+    /// A SARIF 2.1.0 log for code scanning tools, one result per match
+    Sarif,
+    /// Comma-separated path, line, column and match, one row per match
+    Csv,
+    /// Tab-separated path, line, column and match, one row per match
+    Tsv,
+    /// path:line:column:line text, one row per match, for Vim's quickfix
+    /// list (the column counts bytes, as Vim does)
+    Vimgrep,
+}
+
+impl Format {
+    fn dialect(self) -> Option<Dialect> {
+        match self {
+            Format::Csv => Some(Dialect::Csv),
+            Format::Tsv => Some(Dialect::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// Writes results as they arrive from the search pipeline. Every per-line
+/// output mode goes through here so results can be streamed instead of
+/// buffered.
+pub struct Printer<'a> {
+    args: &'a Args,
+    template: Option<&'a Template>,
+    meta_cache: MetaCache,
+    match_count: usize,
+    out: Box<dyn Write>,
+    pager: Option<Pager>,
+    summary: Option<Summary>,
+    /// Set for `--context-block` and `-A`/`-B`/`-C`: the block being
+    /// collected, the file it comes from and how many blocks have been printed.
+    block: Option<PendingBlock>,
+    context: bool,
+    blocks_printed: usize,
+    /// Whether the CSV/TSV header row has been written.
+    header_written: bool,
+    /// The last item of a JSON or SARIF array, held back until it's known
+    /// whether a comma follows.
+    pending_json: Option<String>,
+    /// Set for `--heading`: the file whose heading was printed last.
+    heading: Option<Option<String>>,
+    /// With `--sample`, the number of results the sample was drawn from,
+    /// counted like `match_count`.
+    sampled_from: Option<usize>,
+    sinks: Sinks<'a>,
+    /// For `-l` and `--files-without-match`, the path printed last.
+    listed: Option<Option<String>>,
+    /// `--count-only-dirs` counts by top-level directory.
+    dirs: Option<BTreeMap<String, DirCount>>,
+}
+
+/// `--summary` and `--count` counts: the file currently being printed and
+/// the run so far.
+#[derive(Default)]
+struct Summary {
+    path: Option<String>,
+    file: Tally,
+    total: Tally,
+    files: usize,
+}
+
+/// A `--context-block` block or `-A`/`-B`/`-C` window, printed once no more results can fall in it
+/// so that every match inside is highlighted.
+struct PendingBlock {
+    path: String,
+    lines: Range<usize>,
+    /// The context lines the results brought along, by line number.
+    text: BTreeMap<usize, String>,
+    results: Vec<SearchResult>,
+}
+
+#[derive(Default)]
+struct DirCount {
+    matches: usize,
+    files: usize,
+    last_path: String,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Tally {
+    matches: usize,
+    lines: usize,
+}
+
+/// Built-in `--page-size` paging for terminals without an external pager.
+struct Pager {
+    page_size: usize,
+    shown: usize,
+    remaining: usize,
+    term: Term,
+}
+
+impl Pager {
+    /// Waits for a key once a page is full. Space shows the next page, Enter
+    /// a single result, and `q`/Esc stops the output.
+    fn before_result(&mut self) {
+        if self.shown < self.page_size {
+            self.shown += 1;
+            self.remaining = self.remaining.saturating_sub(1);
+            return;
+        }
+
+        let prompt = i18n::tr("pager-prompt", &[("count", &self.remaining)]);
+        let _ = self.term.write_str(&prompt.reversed().to_string());
+        let key = self.term.read_key();
+        let _ = self.term.clear_line();
+
+        match key {
+            Ok(Key::Enter) => self.shown = self.page_size - 1,
+            Ok(Key::Char('q')) | Ok(Key::Escape) | Err(_) => std::process::exit(0),
+            Ok(_) => self.shown = 0,
+        }
+        self.before_result();
+    }
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(args: &'a Args, template: Option<&'a Template>) -> Printer<'a> {
+        let tty = Term::stdout().is_term();
+        Printer::build(args, template, Box::new(io::stdout()), tty)
+    }
+
+    /// A printer writing to `out` instead of stdout.
+    pub fn with_output(args: &'a Args, template: Option<&'a Template>, out: Box<dyn Write>) -> Printer<'a> {
+        Printer::build(args, template, out, false)
+    }
+
+    /// Headings are on by default when `out` is a terminal.
+    fn build(args: &'a Args, template: Option<&'a Template>, out: Box<dyn Write>, tty: bool) -> Printer<'a> {
+        let heading = args.heading || (tty && !args.no_heading);
+        let plain = args.format == Format::Text && template.is_none() && args.fields.is_none();
+        Printer {
+            args,
+            template,
+            meta_cache: MetaCache::default(),
+            match_count: 0,
+            out,
+            pager: None,
+            summary: ((args.summary || args.count) && plain).then(Summary::default),
+            block: None,
+            context: context::wanted(args),
+            blocks_printed: 0,
+            header_written: false,
+            pending_json: None,
+            heading: (heading && plain).then_some(None),
+            sampled_from: None,
+            sinks: Sinks::new(&args.sink),
+            listed: (args.files_with_matches || args.files_without_match).then_some(None),
+            dirs: args.count_only_dirs.then(BTreeMap::new),
+        }
+    }
+
+    /// Notes that the results printed are a sample of `total` results, for
+    /// the closing count.
+    pub fn sampled(&mut self, total: usize) {
+        self.sampled_from = Some(total);
+    }
+
+    /// Pauses after every `page_size` results out of `total`.
+    pub fn paginate(&mut self, page_size: usize, total: usize) {
+        self.pager = Some(Pager {
+            page_size: page_size.max(1),
+            shown: 0,
+            remaining: total,
+            term: Term::stdout(),
+        });
+    }
+
+    /// Writes one line of output. A closed pipe (e.g. `| head`) ends the
+    /// process quietly, matching what users expect from grep-like tools.
+    fn emit(&mut self, line: fmt::Arguments) {
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                std::process::exit(0);
+            }
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(1);
+        }
+    }
+
+    pub fn print(&mut self, results: &[SearchResult]) {
+        self.match_count += results.len();
+        for result in results {
+            match self.args.files_without_match {
+                true => self.sinks.push_file(&result.file_path),
+                false => self.sinks.push(result),
+            }
+            if let Some(listed) = &mut self.listed {
+                if listed.as_ref() != Some(&result.file_path) {
+                    *listed = Some(result.file_path.clone());
+                    self.emit(format_args!("{}", result.file_path));
+                }
+                continue;
+            }
+            if let Some(dirs) = &mut self.dirs {
+                let count = dirs.entry(top_dir(&self.args.directory, &result.file_path)).or_default();
+                count.matches += result.matches.len();
+                if count.last_path != result.file_path {
+                    count.files += 1;
+                    count.last_path.clone_from(&result.file_path);
+                }
+                continue;
+            }
+            if self.summary.as_ref().is_some_and(|s| s.path.as_ref() != Some(&result.file_path)) {
+                self.end_file();
+            }
+            if let Some(pager) = self.pager.as_mut().filter(|_| !self.args.count) {
+                pager.before_result();
+            }
+            if self.args.count {
+                // Only tallied; see print_file_summary.
+            } else if self.args.format == Format::Json {
+                self.push_item(json::result(result));
+            } else if self.args.format == Format::Sarif {
+                for index in 0..result.spans().len() {
+                    self.push_item(sarif::result(result, index, &self.args.term));
+                }
+            } else if let Some(dialect) = self.args.format.dialect() {
+                self.write_header(dialect);
+                for row in csv::rows(result, dialect) {
+                    self.emit(format_args!("{}", row));
+                }
+            } else if self.args.format == Format::Jsonl {
+                for index in 0..result.spans().len() {
+                    self.emit(format_args!("{}", json::single_match(result, index)));
+                }
+            } else if self.args.format == Format::Vimgrep {
+                for &(start, end) in result.spans() {
+                    let (start, _) = text::snap_span(&result.line, start, end);
+                    let path = &result.file_path;
+                    self.emit(format_args!("{}:{}:{}:{}", path, result.line_number, start + 1, result.line));
+                }
+            } else if let Some(spec) = &self.args.fields {
+                self.emit(format_args!("{}", spec.extract(&result.line)));
+            } else if let Some(template) = self.template {
+                let meta = if template.needs_metadata() {
+                    self.meta_cache.get(&result.file_path)
+                } else {
+                    None
+                };
+                let rows: Vec<String> = (0..result.spans().len())
+                    .map(|index| template.render(result, index, meta))
+                    .collect();
+                for row in rows {
+                    self.emit(format_args!("{}", row));
+                }
+            } else if self.context {
+                self.queue_block(result);
+            } else {
+                self.print_plain(result);
+            }
+            if let Some(summary) = &mut self.summary {
+                summary.path.get_or_insert_with(|| result.file_path.clone());
+                summary.file.matches += result.matches.len();
+                summary.file.lines += 1;
+            }
+        }
+    }
+
+    fn write_header(&mut self, dialect: Dialect) {
+        if !self.header_written {
+            self.header_written = true;
+            self.emit(format_args!("{}", csv::record(csv::HEADER, dialect)));
+        }
+    }
+
+    /// Adds `item` to the JSON or SARIF array, opening the document first.
+    fn push_item(&mut self, item: String) {
+        match self.pending_json.replace(item) {
+            Some(previous) => self.emit(format_args!("{},", previous)),
+            None => {
+                let open = self.array_open();
+                self.emit(format_args!("{}", open));
+            }
+        }
+    }
+
+    fn array_open(&self) -> String {
+        match self.args.format {
+            Format::Sarif => sarif::open(&self.args.term),
+            _ => String::from("["),
+        }
+    }
+
+    fn array_close(&self) -> &'static str {
+        match self.args.format {
+            Format::Sarif => sarif::CLOSE,
+            _ => "]",
+        }
+    }
+
+    /// Adds `result` to the pending block, or prints that block and starts
+    /// the one enclosing `result`.
+    fn queue_block(&mut self, result: &SearchResult) {
+        let index = result.line_number.saturating_sub(1);
+        let around = context::lines_around(self.args);
+        let lines = match around {
+            Some((before, after)) => index.saturating_sub(before)..index + after + 1,
+            None => {
+                let numbers = result.context.iter().map(|&(number, _)| number - 1).chain([index]);
+                numbers.clone().min().unwrap_or(index)..numbers.max().unwrap_or(index) + 1
+            }
+        };
+        if let Some(block) = self.block.as_mut().filter(|block| same_text(&block.results[0], result)) {
+            // Windows that overlap or touch merge into one group.
+            let joins = match around {
+                Some(_) => lines.start <= block.lines.end && index >= block.lines.start,
+                None => block.lines.contains(&index),
+            };
+            if joins {
+                block.lines.end = block.lines.end.max(lines.end);
+                block.text.extend(result.context.iter().cloned());
+                block.results.push(result.clone());
+                return;
+            }
+        }
+        self.flush_block();
+        let text = result.context.iter().cloned().collect();
+        self.block = Some(PendingBlock { path: result.file_path.clone(), lines, text, results: vec![result.clone()] });
+    }
+
+    /// Prints the pending block: its matching lines as usual, the others as
+    /// context, with `--` between blocks.
+    fn flush_block(&mut self) {
+        let Some(block) = self.block.take() else { return };
+
+        // A new heading already separates blocks from different files.
+        let new_heading = self.heading.as_ref().is_some_and(|last| last.as_ref() != Some(&block.path));
+        if self.blocks_printed > 0 && !new_heading {
+            self.emit(format_args!("{}", "--".dimmed()));
+        }
+        self.blocks_printed += 1;
+
+        let mut results = block.results.iter().peekable();
+        for line_number in block.lines.start + 1..=block.lines.end {
+            let mut matched = false;
+            while let Some(result) = results.next_if(|r| r.line_number == line_number) {
+                self.print_plain(result);
+                matched = true;
+            }
+            // Lines past the end of the file have no text.
+            if let Some(line) = block.text.get(&line_number).filter(|_| !matched) {
+                let location = self.location(&block.path, line_number, true);
+                self.emit(format_args!("{}{}", location, line));
+            }
+        }
+        // Out-of-order results.
+        for result in results {
+            self.print_plain(result);
+        }
+    }
+
+    /// Finishes the output for the current file.
+    fn end_file(&mut self) {
+        self.flush_block();
+        self.print_file_summary();
+    }
+
+    /// Ends the current file's group with its `--summary` line, or prints
+    /// its `--count` line.
+    fn print_file_summary(&mut self) {
+        let Some(summary) = &mut self.summary else { return };
+        let Some(path) = summary.path.take() else { return };
+        let file = std::mem::take(&mut summary.file);
+        summary.total.matches += file.matches;
+        summary.total.lines += file.lines;
+        summary.files += 1;
+        if self.args.count {
+            let lines = file.lines.to_string().themed(theme::palette().count);
+            self.emit(format_args!("{}{}{}", path.themed(theme::palette().path), self.args.separator, lines));
+            return;
+        }
+        let line = i18n::tr("file-summary", &[("matches", &file.matches), ("lines", &file.lines)]);
+        self.emit(format_args!("  {}", line.dimmed()));
+    }
+
+    fn print_plain(&mut self, result: &SearchResult) {
+        // Binary matches give their offset rather than raw bytes.
+        if let Some(offset) = result.byte_offset {
+            let text = i18n::tr("binary-match", &[("offset", &format!("0x{:X}", offset))]);
+            if self.start_heading(&result.file_path) {
+                self.emit(format_args!("{}", text.dimmed()));
+            } else {
+                let path = result.file_path.themed(theme::palette().path);
+                self.emit(format_args!("{}{} {}", path, self.args.separator, text.dimmed()));
+            }
+            if let Some(context) = self.args.hex_context {
+                let len = result.matches.first().map_or(0, |&(start, end)| end - start);
+                match binary::hex_dump(&result.file_path, offset, len, context) {
+                    Ok(rows) => rows.iter().for_each(|row| self.emit(format_args!("  {}", row))),
+                    Err(e) => eprintln!("{}", i18n::error(e).red()),
+                }
+            }
+            return;
+        }
+        let mut meta = if self.args.show_meta.is_empty() {
+            String::new()
+        } else {
+            let meta = self.meta_cache.get(&result.file_path);
+            format!("  [{}]", meta::describe(meta, &self.args.show_meta)).dimmed().to_string()
+        };
+        if let Some(origin) = &result.origin {
+            meta.insert_str(0, &format!("  ({})", origin).dimmed().to_string());
+        }
+        // Only matching lines are shown, so block comments opened on earlier
+        // lines can't be tracked; each line is tokenized on its own.
+        let (text, matches) = match self.args.snippet_chars {
+            Some(chars) => text::snippet(&result.line, &result.matches, chars.get()),
+            None => (result.line.clone(), result.matches.clone()),
+        };
+        let line = match highlight::detect(&result.file_path).filter(|_| self.args.pretty) {
+            Some(syntax) => highlight::highlight(&text, syntax, &mut State::default(), &matches, &result.terms),
+            None => highlight_matches(&text, &matches, &result.terms),
+        };
+        let location = self.location(&result.file_path, result.line_number, false);
+        self.emit(format_args!("{}{}{}", location, line, meta));
+    }
+
+    /// `path:line ` before a result (`path-line ` for context lines), or
+    /// just the line number under a heading, printing the heading first when
+    /// `path` starts a new group. `--separator`, `--line-number-width` and
+    /// `--no-line-number` adjust it.
+    fn location(&mut self, path: &str, line_number: usize, context: bool) -> String {
+        let palette = theme::palette();
+        let number = if self.args.no_line_number {
+            String::new()
+        } else {
+            let number = format!("{:>width$}", line_number, width = self.args.line_number_width);
+            match context {
+                true => number.dimmed().to_string(),
+                false => number.themed(palette.line_number).to_string(),
+            }
+        };
+        let separator = if context { "-" } else { self.args.separator.as_str() };
+        if !self.start_heading(path) {
+            return format!("{}{}{} ", path.themed(palette.path), separator, number);
+        }
+        match number.is_empty() {
+            true => String::new(),
+            false => format!("{}{} ", number, separator),
+        }
+    }
+
+    /// Prints `path` as a heading if it starts a new group. Returns whether
+    /// headings are on.
+    fn start_heading(&mut self, path: &str) -> bool {
+        let Some(last) = &mut self.heading else { return false };
+        if last.as_deref() != Some(path) {
+            let blank = if last.is_some() { "\n" } else { "" };
+            *last = Some(path.to_string());
+            self.emit(format_args!("{}{}", blank, path.themed(theme::palette().path)));
+        }
+        true
+    }
+
+    /// Prints the closing summary for the human-readable format.
+    pub fn finish(mut self) {
+        self.write_footer();
+        if let Err(e) = self.sinks.flush() {
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(1);
+        }
+        if let Err(e) = self.out.flush() {
+            eprintln!("{}", i18n::error(e).red());
+            std::process::exit(1);
+        }
+    }
+
+    fn write_footer(&mut self) {
+        self.end_file();
+        if matches!(self.args.format, Format::Json | Format::Sarif) {
+            let close = self.array_close();
+            match self.pending_json.take() {
+                Some(last) => self.emit(format_args!("{}\n{}", last, close)),
+                None => {
+                    let open = self.array_open();
+                    self.emit(format_args!("{}{}", open, close));
+                }
+            }
+            return;
+        }
+        if let Some(dialect) = self.args.format.dialect() {
+            self.write_header(dialect);
+            return;
+        }
+        if let Some(dirs) = self.dirs.take() {
+            for (dir, count) in dirs {
+                let line = i18n::tr("dir-summary", &[("matches", &count.matches), ("files", &count.files)]);
+                self.emit(format_args!("{}  {}", dir.themed(theme::palette().path), line.dimmed()));
+            }
+        }
+        // Lists of paths stay clean for xargs.
+        if self.listed.is_some() {
+            return;
+        }
+        if matches!(self.args.format, Format::Jsonl | Format::Vimgrep) || self.template.is_some() || self.args.fields.is_some() {
+            return;
+        }
+        if self.match_count == 0 && self.sampled_from.unwrap_or(0) == 0 {
+            self.emit(format_args!("{}", i18n::tr("no-matches", &[]).themed(theme::palette().notice)));
+        } else if let Some(summary) = self.summary.as_ref().filter(|_| self.args.count) {
+            let total = i18n::tr("count-total", &[("lines", &summary.total.lines), ("files", &summary.files)]);
+            self.emit(format_args!("\n{}", total.themed(theme::palette().count)));
+        } else if let Some(summary) = &self.summary {
+            let total = i18n::tr("total-summary", &[
+                ("matches", &summary.total.matches),
+                ("lines", &summary.total.lines),
+                ("files", &summary.files),
+            ]);
+            self.emit(format_args!("\n{}", total.themed(theme::palette().count)));
+        } else if let Some(total) = self.sampled_from {
+            let count = total.to_string().themed(theme::palette().count).to_string();
+            let line = i18n::tr("sampled-matches", &[("shown", &self.match_count), ("count", &count)]);
+            self.emit(format_args!("\n{}", line));
+        } else {
+            let count = self.match_count.to_string().themed(theme::palette().count).to_string();
+            self.emit(format_args!("\n{}", i18n::tr("matches-found", &[("count", &count)])));
+        }
+    }
+}
+
+/// Whether the line numbers of `a` and `b` count lines of the same text:
+/// the same file, or the same cell source or output of a notebook.
+fn same_text(a: &SearchResult, b: &SearchResult) -> bool {
+    a.file_path == b.file_path && a.cell == b.cell && (a.cell.is_none() || a.origin == b.origin)
+}
+
+/// The first component of `path` below `directory`, with a trailing `/`,
+/// or `.` for files directly in it.
+fn top_dir(directory: &str, path: &str) -> String {
+    let relative = Path::new(path).strip_prefix(directory).unwrap_or(Path::new(path));
+    let mut components = relative.components().filter(|c| !matches!(c, Component::CurDir));
+    match (components.next(), components.next()) {
+        (Some(first), Some(_)) => format!("{}/", first.as_os_str().to_string_lossy()),
+        _ => ".".to_string(),
+    }
+}
+
+/// Highlights `matches` (byte ranges) in `line`, each in the color of the
+/// term in `terms` that produced it. Ranges are snapped to grapheme
+/// boundaries first, so a span never splits a character or its combining
+/// marks.
+fn highlight_matches(line: &str, matches: &[(usize, usize)], terms: &[usize]) -> String {
+    let mut result = String::new();
+    let mut last_end = 0;
+
+    for (index, &(start, end)) in matches.iter().enumerate() {
+        let (start, end) = text::snap_span(line, start.max(last_end), end);
+        if start >= end {
+            continue;
+        }
+        result.push_str(&line[last_end..start]);
+        let term = terms.get(index).copied().unwrap_or(0);
+        result.push_str(&theme::palette().paint_term(line[start..end].normal(), term).to_string());
+        last_end = end;
+    }
+    result.push_str(&line[last_end..]);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marked(text: &str) -> String {
+        theme::palette().paint_match(text.normal()).to_string()
+    }
+
+    fn highlight(line: &str, matches: &[(usize, usize)]) -> String {
+        colored::control::set_override(true);
+        highlight_matches(line, matches, &[])
+    }
+
+    #[test]
+    fn highlights_ascii_spans() {
+        assert_eq!(highlight("foo bar", &[(4, 7)]), format!("foo {}", marked("bar")));
+    }
+
+    #[test]
+    fn highlights_cjk_on_char_boundaries() {
+        let line = "検索する文字列";
+        // "する" is bytes 6..12; an end offset inside "文" is widened to include it.
+        assert_eq!(highlight(line, &[(6, 12)]), format!("検索{}文字列", marked("する")));
+        assert_eq!(highlight(line, &[(6, 13)]), format!("検索{}字列", marked("する文")));
+    }
+
+    #[test]
+    fn offsets_inside_emoji_do_not_panic() {
+        let line = "a😀b";
+        assert_eq!(highlight(line, &[(2, 3)]), format!("a{}b", marked("😀")));
+    }
+
+    #[test]
+    fn keeps_zwj_emoji_sequences_whole() {
+        let family = "👨\u{200D}👩\u{200D}👧";
+        let line = format!("x{}y", family);
+        // A match covering only the first person extends to the whole family.
+        assert_eq!(highlight(&line, &[(1, 5)]), format!("x{}y", marked(family)));
+    }
+
+    #[test]
+    fn includes_trailing_combining_marks() {
+        // "e" followed by U+0301 COMBINING ACUTE ACCENT.
+        let line = "cafe\u{301}!";
+        assert_eq!(highlight(line, &[(3, 4)]), format!("caf{}!", marked("e\u{301}")));
+        // A start on the combining mark moves back to its base letter.
+        assert_eq!(highlight(line, &[(4, 6)]), format!("caf{}!", marked("e\u{301}")));
+    }
+
+    #[test]
+    fn clamps_offsets_past_the_end() {
+        // A span running past the line is clamped rather than sliced.
+        assert_eq!(highlight("İ", &[(0, 3)]), marked("İ"));
+    }
+}
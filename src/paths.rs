@@ -0,0 +1,92 @@
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use clap::ValueEnum;
+
+/// How file paths are written in the output.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PathStyle {
+    /// Relative to the current directory
+    Relative,
+    /// Absolute, without resolving symlinks
+    Absolute,
+    /// Absolute with symlinks and `..` resolved
+    Canonical,
+}
+
+/// Rewrites `path` according to `style`, falling back to the path as found
+/// when it cannot be resolved.
+pub fn format_path(path: &str, style: PathStyle) -> String {
+    let rewritten = match style {
+        PathStyle::Relative => env::current_dir().ok().map(|cwd| relative_to(&absolute(path, &cwd), &cwd)),
+        PathStyle::Absolute => env::current_dir().ok().map(|cwd| absolute(path, &cwd)),
+        PathStyle::Canonical => fs::canonicalize(path).ok(),
+    };
+    rewritten
+        .and_then(|p| p.to_str().map(str::to_string))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Joins `path` onto `cwd` and lexically removes `.` and `..` components.
+fn absolute(path: &str, cwd: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<_> = path.components().collect();
+    let base: Vec<_> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+
+    // Paths on different Windows drives have no relative form.
+    if common == 0 {
+        return path.iter().collect();
+    }
+
+    let mut result = PathBuf::new();
+    for _ in common..base.len() {
+        result.push("..");
+    }
+    for component in &path[common..] {
+        result.push(component);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn absolute_joins_and_normalizes() {
+        let cwd = Path::new("/home/me/proj");
+        assert_eq!(absolute("src/./a.rs", cwd), Path::new("/home/me/proj/src/a.rs"));
+        assert_eq!(absolute("../other/b.rs", cwd), Path::new("/home/me/other/b.rs"));
+        assert_eq!(absolute("/etc/hosts", cwd), Path::new("/etc/hosts"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_climbs_out_of_the_base() {
+        let base = Path::new("/home/me/proj");
+        assert_eq!(relative_to(Path::new("/home/me/proj/src/a.rs"), base), Path::new("src/a.rs"));
+        assert_eq!(relative_to(Path::new("/home/me/other/b.rs"), base), Path::new("../other/b.rs"));
+        assert_eq!(relative_to(Path::new("/etc/hosts"), base), Path::new("../../../etc/hosts"));
+    }
+
+    #[test]
+    fn unresolvable_paths_are_kept() {
+        assert_eq!(format_path("no/such/file", PathStyle::Canonical), "no/such/file");
+    }
+}
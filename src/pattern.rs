@@ -0,0 +1,403 @@
+use std::fmt::Write;
+use std::fs;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::literal::{ExtractKind, Extractor, Seq};
+
+use crate::fuzzy::Fuzzy;
+use crate::Args;
+
+/// The regex source searched for one `--term`: the term itself with
+/// `--regex`, otherwise the term with every metacharacter escaped.
+fn source(term: &str, args: &Args) -> String {
+    let source = if args.regex {
+        term.to_string()
+    } else {
+        regex::escape(term)
+    };
+    // Half boundaries only require a non-word character (or the edge of the
+    // line) outside the match, so `--word` also works for terms that start
+    // or end with punctuation, where `\b` would never match.
+    if args.word {
+        format!(r"\b{{start-half}}(?:{})\b{{end-half}}", source)
+    } else {
+        source
+    }
+}
+
+/// The regex for all terms together: one term as is, several as an
+/// alternation, so the earliest term listed wins when two match at the same
+/// position.
+fn combined_source(args: &Args) -> String {
+    match args.term.as_slice() {
+        [term] => source(term, args),
+        terms => terms.iter().map(|term| format!("(?:{})", source(term, args))).collect::<Vec<_>>().join("|"),
+    }
+}
+
+/// What `--all-of` requires every term to match within.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum AllOf {
+    /// Report only lines on which every term matches
+    Line,
+    /// Report only files in which every term matches somewhere
+    File,
+}
+
+/// Finds matches of every `--term` in a line and tells which term each
+/// came from. When there are several plain-text terms, one Aho-Corasick pass
+/// finds them all; otherwise the combined regex runs.
+pub struct Matcher {
+    regex: Regex,
+    /// `--all-of line`: lines only match when every term does.
+    all_on_line: bool,
+    /// Each term on its own, to attribute regex matches when there are several.
+    terms: Vec<Regex>,
+    literals: Option<AhoCorasick>,
+    /// Set for `--fuzzy`, which replaces the regex for finding matches.
+    fuzzy: Vec<Fuzzy>,
+}
+
+impl Matcher {
+    /// The regex matching any term, for captures and byte searches.
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// The spans of all matches in `line`, and the index of the term that
+    /// produced each.
+    pub fn find(&self, line: &str) -> (Vec<(usize, usize)>, Vec<usize>) {
+        let (spans, terms): (Vec<_>, Vec<_>) = match &self.literals {
+            _ if !self.fuzzy.is_empty() => self.find_fuzzy(line),
+            Some(literals) => literals.find_iter(line).map(|m| ((m.start(), m.end()), m.pattern().as_usize())).unzip(),
+            None => self
+                .regex
+                .find_iter(line)
+                .map(|m| ((m.start(), m.end()), self.term_at(line, m.start())))
+                .unzip(),
+        };
+        if self.all_on_line && !spans.is_empty() && !self.covers_all(&self.terms_in(line)) {
+            return (Vec::new(), Vec::new());
+        }
+        (spans, terms)
+    }
+
+    /// Every term that matches somewhere in `line`, in order. Unlike the
+    /// terms `find` reports, this counts a term whose only match overlaps
+    /// another term's, as `bcd` does `abc` in `abcd`.
+    pub fn terms_in(&self, line: &str) -> Vec<usize> {
+        if !self.fuzzy.is_empty() {
+            return (0..self.fuzzy.len()).filter(|&term| !self.fuzzy[term].find_iter(line).is_empty()).collect();
+        }
+        if self.terms.is_empty() {
+            return match self.regex.is_match(line) {
+                true => vec![0],
+                false => Vec::new(),
+            };
+        }
+        (0..self.terms.len()).filter(|&term| self.terms[term].is_match(line)).collect()
+    }
+
+    /// Approximate matches of every term, leftmost first; where matches of
+    /// different terms overlap, the earlier one wins.
+    fn find_fuzzy(&self, line: &str) -> (Vec<(usize, usize)>, Vec<usize>) {
+        let mut found: Vec<((usize, usize), usize)> = self
+            .fuzzy
+            .iter()
+            .enumerate()
+            .flat_map(|(term, fuzzy)| fuzzy.find_iter(line).into_iter().map(move |span| (span, term)))
+            .collect();
+        found.sort_by_key(|&((start, _), term)| (start, term));
+        let mut end = 0;
+        found.retain(|&((start, stop), _)| {
+            let keep = start >= end;
+            if keep {
+                end = stop;
+            }
+            keep
+        });
+        found.into_iter().unzip()
+    }
+
+    /// Whether `terms` includes every term at least once.
+    pub fn covers_all(&self, terms: &[usize]) -> bool {
+        let mut seen = self.term_set();
+        terms.iter().for_each(|&term| seen.insert(term));
+        seen.is_full()
+    }
+
+    /// An empty set of this matcher's terms.
+    pub fn term_set(&self) -> TermSet {
+        let len = self.terms.len().max(1);
+        TermSet { bits: vec![0; len.div_ceil(64)], missing: len }
+    }
+
+    /// The regex source of each term when there are several, for searches
+    /// that need their own regexes, such as over bytes.
+    pub fn term_sources(&self) -> impl Iterator<Item = &str> {
+        self.terms.iter().map(Regex::as_str)
+    }
+
+    /// The first term matching at `start` in `line`, which is the one the
+    /// combined regex chose there.
+    pub fn term_at(&self, line: &str, start: usize) -> usize {
+        if self.terms.len() < 2 {
+            return 0;
+        }
+        self.terms
+            .iter()
+            .position(|term| term.find_at(line, start).is_some_and(|m| m.start() == start))
+            .unwrap_or(0)
+    }
+}
+
+/// The terms that have matched so far, say within a file, one bit each.
+pub struct TermSet {
+    bits: Vec<u64>,
+    missing: usize,
+}
+
+impl TermSet {
+    pub fn insert(&mut self, term: usize) {
+        let (word, bit) = (term / 64, 1 << (term % 64));
+        if self.bits[word] & bit == 0 {
+            self.bits[word] |= bit;
+            self.missing -= 1;
+        }
+    }
+
+    /// Whether every term has matched.
+    pub fn is_full(&self) -> bool {
+        self.missing == 0
+    }
+}
+
+/// Compiles the search pattern. Errors carry the regex crate's diagnostic,
+/// which points at the offending position in the pattern.
+pub fn compile(args: &Args) -> Result<Matcher, String> {
+    let regex = build(&combined_source(args), args)?;
+    let terms = match args.term.len() {
+        1 => Vec::new(),
+        _ => args.term.iter().map(|term| build(&source(term, args), args)).collect::<Result<_, _>>()?,
+    };
+    let literals = if uses_literals(args) {
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .ascii_case_insensitive(!args.case_sensitive)
+            .build(&args.term);
+        Some(automaton.map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let fuzzy = match args.fuzzy {
+        Some(distance) => {
+            args.term.iter().map(|term| Fuzzy::new(term, distance, !args.case_sensitive)).collect::<Result<_, _>>()?
+        }
+        None => Vec::new(),
+    };
+    Ok(Matcher { regex, all_on_line: args.all_of == Some(AllOf::Line), terms, literals, fuzzy })
+}
+
+/// Case is ignored by the regex engine, with Unicode simple case folding,
+/// so match spans always index into the line as it is in the file.
+fn build(source: &str, args: &Args) -> Result<Regex, String> {
+    RegexBuilder::new(source).case_insensitive(!args.case_sensitive).build().map_err(|e| e.to_string())
+}
+
+/// Whether several terms are all plain text, so Aho-Corasick can look for
+/// them in one pass. It only folds ASCII case, so ignoring case needs ASCII
+/// terms.
+fn uses_literals(args: &Args) -> bool {
+    let foldable = args.case_sensitive || args.term.iter().all(|term| term.is_ascii());
+    args.term.len() > 1 && !args.regex && !args.word && args.fuzzy.is_none() && foldable
+}
+
+/// Whether `term` has an uppercase letter, for `--smart-case`. In a regex,
+/// escapes such as `\W` or `\P{Greek}` don't count.
+pub fn has_uppercase(term: &str, regex: bool) -> bool {
+    if !regex {
+        return term.chars().any(char::is_uppercase);
+    }
+    let mut chars = term.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.next(), Some('p' | 'P')) && chars.clone().next() == Some('{') => {
+                chars.by_ref().take_while(|&c| c != '}').for_each(drop);
+            }
+            '\\' => {}
+            c if c.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Reads one term per line from `path`, skipping blank lines and `#`
+/// comments. Other whitespace is kept, since it may be part of a term.
+pub fn read_pattern_file(path: &str) -> Result<Vec<String>, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let terms: Vec<String> = source
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if terms.is_empty() {
+        return Err(format!("{}: no patterns", path));
+    }
+    Ok(terms)
+}
+
+/// Describes how the terms are turned into the regex that runs: the
+/// transformations applied, the matching strategy and the literals used to
+/// skip quickly past lines that can't match.
+pub fn explain(args: &Args) -> Result<String, String> {
+    let source = combined_source(args);
+    let regex = build(&source, args)?;
+    // Parsed with the flags `build` compiles with, so the literals shown
+    // are the ones the search uses.
+    let hir = regex_syntax::ParserBuilder::new()
+        .case_insensitive(!args.case_sensitive)
+        .build()
+        .parse(&source)
+        .map_err(|e| e.to_string())?;
+    let prefixes = Extractor::new().kind(ExtractKind::Prefix).extract(&hir);
+    let suffixes = Extractor::new().kind(ExtractKind::Suffix).extract(&hir);
+
+    let mut out = String::new();
+    for term in &args.term {
+        let _ = writeln!(out, "Pattern:     {}", term);
+    }
+    if args.regex {
+        let _ = writeln!(out, "Syntax:      regular expression (--regex)");
+    } else {
+        let _ = writeln!(out, "Syntax:      literal text; metacharacters escaped");
+    }
+    let _ = writeln!(out, "Compiled:    {}", regex.as_str());
+    if args.case_sensitive {
+        let _ = writeln!(out, "Case:        sensitive");
+    } else {
+        let _ = writeln!(out, "Case:        insensitive, by Unicode simple case folding in the regex engine");
+    }
+    if args.word {
+        let _ = writeln!(out, "Words:       whole words only (--word)");
+    }
+    let _ = writeln!(out, "Groups:      {}", regex.captures_len() - 1);
+    if let Some(distance) = args.fuzzy {
+        let _ = writeln!(out, "Engine:      approximate matching, at most {} edits per match (--fuzzy)", distance);
+    } else if uses_literals(args) {
+        let _ = writeln!(out, "Engine:      Aho-Corasick, all {} terms in one pass", args.term.len());
+    } else {
+        let _ = writeln!(out, "Engine:      {}", engine(&prefixes));
+    }
+    let _ = writeln!(out, "Prefixes:    {}", describe_literals(&prefixes));
+    let _ = writeln!(out, "Suffixes:    {}", describe_literals(&suffixes));
+    Ok(out)
+}
+
+fn engine(prefixes: &Seq) -> &'static str {
+    match prefixes.literals() {
+        Some([literal]) if prefixes.is_exact() && !literal.is_empty() => "substring search (the pattern is a plain literal)",
+        Some(literals) if prefixes.is_exact() && !literals.is_empty() => "multi-literal search (Aho-Corasick / Teddy)",
+        Some(literals) if !literals.is_empty() && literals.iter().all(|l| !l.is_empty()) => {
+            "lazy DFA, with a literal prefilter to find candidate positions"
+        }
+        _ => "lazy DFA / PikeVM without a prefilter; every position is tried",
+    }
+}
+
+fn describe_literals(seq: &Seq) -> String {
+    match seq.literals() {
+        None => "none (too many or unbounded)".to_string(),
+        Some(literals) if literals.is_empty() || literals.iter().all(|l| l.is_empty()) => "none".to_string(),
+        Some(literals) => {
+            let shown: Vec<String> = literals
+                .iter()
+                .take(8)
+                .map(|l| format!("{:?}", String::from_utf8_lossy(l.as_bytes())))
+                .collect();
+            let more = if literals.len() > 8 { format!(" and {} more", literals.len() - 8) } else { String::new() };
+            let exact = if seq.is_exact() { " (exact)" } else { "" };
+            format!("{}{}{}", shown.join(", "), more, exact)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn matcher(flags: &[&str]) -> Matcher {
+        let args = Args::parse_from(["fsearch"].iter().chain(flags));
+        compile(&args).unwrap()
+    }
+
+    fn spans(flags: &[&str], line: &str) -> Vec<(usize, usize)> {
+        matcher(flags).find(line).0
+    }
+
+    #[test]
+    fn spans_index_the_original_line() {
+        // "İ" lowercases to three bytes, which used to shift every span after it.
+        assert_eq!(spans(&["-t", "foo"], "İ FOO"), vec![(3, 6)]);
+        assert_eq!(spans(&["-t", "FOO"], "İ foo"), vec![(3, 6)]);
+    }
+
+    #[test]
+    fn folds_non_ascii_case() {
+        let line = "ΣΊΣΥΦΟΣ";
+        assert_eq!(spans(&["-t", "σίσυφος"], line), vec![(0, line.len())]);
+        assert_eq!(spans(&["-t", "ÄRGER"], "kein ärger"), vec![(5, 11)]);
+    }
+
+    #[test]
+    fn case_sensitive_keeps_case() {
+        assert!(spans(&["-c", "-t", "foo"], "FOO").is_empty());
+        assert_eq!(spans(&["-c", "-t", "FOO"], "foo FOO"), vec![(4, 7)]);
+    }
+
+    #[test]
+    fn non_ascii_terms_skip_aho_corasick() {
+        let flags = ["-t", "größe", "-t", "x"];
+        assert!(matcher(&flags).literals.is_none());
+        assert_eq!(spans(&flags, "GRÖSSE GRÖßE"), vec![(8, 15)]);
+        assert!(matcher(&["-t", "size", "-t", "x"]).literals.is_some());
+        assert_eq!(spans(&["-t", "size", "-t", "x"], "SIZE X"), vec![(0, 4), (5, 6)]);
+    }
+
+    #[test]
+    fn fuzzy_ignores_case() {
+        assert_eq!(spans(&["--fuzzy", "-t", "recieve"], "Émile RECEIVES"), vec![(7, 14)]);
+        assert!(spans(&["-c", "--fuzzy", "-t", "recieve"], "RECEIVES").is_empty());
+    }
+
+    #[test]
+    fn all_of_line_counts_overlapping_terms() {
+        let flags = ["-t", "abc", "-t", "bcd", "--all-of", "line"];
+        assert_eq!(spans(&flags, "abcd"), vec![(0, 3)]);
+        assert!(spans(&flags, "abc").is_empty());
+        assert_eq!(spans(&["-x", "-t", "ab.", "-t", "b.d", "--all-of", "line"], "abcd"), vec![(0, 3)]);
+        assert_eq!(matcher(&["-t", "abc", "-t", "bcd"]).terms_in("xabcd"), [0, 1]);
+        assert_eq!(matcher(&["--fuzzy", "-t", "abcx", "-t", "bcdx"]).terms_in("abcd"), [0, 1]);
+    }
+
+    #[test]
+    fn explain_follows_case_folding() {
+        let explained = |flags: &[&str]| explain(&Args::parse_from(["fsearch"].iter().chain(flags))).unwrap();
+        let sensitive = explained(&["-c", "-t", "Ab"]);
+        assert!(sensitive.contains("Prefixes:    \"Ab\" (exact)"), "{}", sensitive);
+        assert!(sensitive.contains("Engine:      substring search"), "{}", sensitive);
+        let insensitive = explained(&["-t", "Ab"]);
+        assert!(insensitive.contains("Prefixes:    \"AB\", \"Ab\", \"aB\", \"ab\" (exact)"), "{}", insensitive);
+        assert!(insensitive.contains("Engine:      multi-literal search"), "{}", insensitive);
+    }
+
+    #[test]
+    fn uppercase_in_escapes_is_not_smart_case() {
+        assert!(!has_uppercase(r"\W+\p{Lu}", true));
+        assert!(has_uppercase(r"\WFoo", true));
+        assert!(has_uppercase("Ärger", false));
+    }
+}
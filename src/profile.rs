@@ -0,0 +1,158 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+
+use crate::theme::{self, Themed};
+
+/// Time spent on one file, split by phase.
+#[derive(Debug, Default, Clone)]
+pub struct FileProfile {
+    pub path: String,
+    pub open: Duration,
+    pub read: Duration,
+    pub search: Duration,
+}
+
+impl FileProfile {
+    pub fn total(&self) -> Duration {
+        self.open + self.read + self.search
+    }
+}
+
+/// Collects `--profile` timings. All methods are no-ops when disabled so the
+/// search path can call them unconditionally.
+pub struct Profiler {
+    enabled: bool,
+    walk: Mutex<Duration>,
+    files: Mutex<Vec<FileProfile>>,
+}
+
+impl Profiler {
+    pub fn new(enabled: bool) -> Profiler {
+        Profiler {
+            enabled,
+            walk: Mutex::new(Duration::ZERO),
+            files: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs `f`, adding its wall time to `slot` when profiling.
+    pub fn time<T>(&self, slot: &mut Duration, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+        let start = Instant::now();
+        let value = f();
+        *slot += start.elapsed();
+        value
+    }
+
+    pub fn record_walk(&self, elapsed: Duration) {
+        if self.enabled {
+            *self.walk.lock().unwrap() += elapsed;
+        }
+    }
+
+    pub fn record_file(&self, profile: FileProfile) {
+        if self.enabled {
+            self.files.lock().unwrap().push(profile);
+        }
+    }
+
+    /// Prints the aggregate phase breakdown and the `top` slowest files to
+    /// stderr, so the report never mixes with piped results.
+    pub fn report(&self, output: Duration, top: usize) {
+        if self.enabled {
+            let _ = self.write_report(&mut io::stderr().lock(), output, top);
+        }
+    }
+
+    fn write_report(&self, out: &mut impl Write, output: Duration, top: usize) -> io::Result<()> {
+        let mut files = std::mem::take(&mut *self.files.lock().unwrap());
+        let walk = *self.walk.lock().unwrap();
+        let open: Duration = files.iter().map(|f| f.open).sum();
+        let read: Duration = files.iter().map(|f| f.read).sum();
+        let search: Duration = files.iter().map(|f| f.search).sum();
+
+        writeln!(out, "\n{}", "Profile".themed(theme::palette().count).bold())?;
+        writeln!(out, "  files searched  {}", files.len())?;
+        // Per-file phases are summed across threads, so they can exceed wall time.
+        for (phase, elapsed) in [("walk", walk), ("open", open), ("read", read), ("match", search), ("output", output)] {
+            writeln!(out, "  {:<14}  {:>10.3} ms", phase, elapsed.as_secs_f64() * 1000.0)?;
+        }
+
+        files.sort_by_key(|f| std::cmp::Reverse(f.total()));
+        if !files.is_empty() && top > 0 {
+            writeln!(out, "\n  {} slowest files (open / read / match ms):", top.min(files.len()))?;
+            for file in files.iter().take(top) {
+                writeln!(
+                    out,
+                    "  {:>10.3}  {:>8.3} / {:>8.3} / {:>8.3}  {}",
+                    file.total().as_secs_f64() * 1000.0,
+                    file.open.as_secs_f64() * 1000.0,
+                    file.read.as_secs_f64() * 1000.0,
+                    file.search.as_secs_f64() * 1000.0,
+                    file.path.themed(theme::palette().path)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, millis: u64) -> FileProfile {
+        FileProfile { path: path.to_string(), search: Duration::from_millis(millis), ..Default::default() }
+    }
+
+    fn strip_ansi(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn disabled_profiler_records_nothing() {
+        let profiler = Profiler::new(false);
+        let mut slot = Duration::ZERO;
+        assert_eq!(profiler.time(&mut slot, || 7), 7);
+        profiler.record_walk(Duration::from_secs(1));
+        profiler.record_file(file("a", 5));
+        assert_eq!(slot, Duration::ZERO);
+        assert!(profiler.files.lock().unwrap().is_empty());
+        assert_eq!(*profiler.walk.lock().unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn report_lists_the_slowest_files_first() {
+        let profiler = Profiler::new(true);
+        for (path, millis) in [("fast", 1), ("slow", 30), ("middle", 10)] {
+            profiler.record_file(file(path, millis));
+        }
+        let mut out = Vec::new();
+        profiler.write_report(&mut out, Duration::ZERO, 2).unwrap();
+        let report = strip_ansi(&String::from_utf8(out).unwrap());
+
+        assert!(report.contains("files searched  3"), "{}", report);
+        assert!(report.contains("41.000 ms"), "{}", report);
+        let listed: Vec<&str> = report
+            .lines()
+            .skip_while(|line| !line.contains("slowest files"))
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().last())
+            .collect();
+        assert_eq!(listed, ["slow", "middle"]);
+    }
+}
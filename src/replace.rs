@@ -0,0 +1,447 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use colored::Colorize;
+use regex::Regex;
+
+use crate::diff;
+use crate::i18n;
+use crate::theme::{self, Themed};
+use crate::{Args, SearchResult};
+
+/// Numbers the temporary files of this process, which `write` skips past
+/// when one is taken.
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// A file's contents before and after replacing the matches on some of its
+/// lines.
+pub struct Rewrite {
+    pub path: String,
+    pub original: String,
+    pub replaced: String,
+    /// The lines that changed, in order.
+    pub changes: Vec<Change>,
+    /// Matches replaced on them.
+    pub occurrences: usize,
+}
+
+/// A changed line: its index in the original and its new text, including
+/// the line ending (the replacement may have split it into several lines).
+pub struct Change {
+    pub index: usize,
+    pub text: String,
+}
+
+/// The reported match spans of some lines of a file, by line number.
+pub type LineSpans = BTreeMap<usize, Vec<(usize, usize)>>;
+
+/// A match about to be replaced, for `--interactive` to confirm.
+pub struct Proposal<'a> {
+    /// Every line of the file, with line endings.
+    pub lines: &'a [&'a str],
+    pub index: usize,
+    /// The match, as a byte span of `lines[index]`.
+    pub span: (usize, usize),
+    pub replacement: &'a str,
+}
+
+/// Replaces the matches of `regex` that the search reported, given as byte
+/// spans of each line (numbered from 1) of the file at `path`, with
+/// `template`, where `$1` and `${name}` expand as in `Regex::replace`, as
+/// far as `accept` agrees to each one. Other matches on those lines (outside
+/// `--scope`, say) are left alone. Each line keeps its own line ending; line
+/// breaks in the replacement use the file's dominant one. `None` when
+/// nothing changes.
+pub fn rewrite(
+    path: &str,
+    spans: &LineSpans,
+    regex: &Regex,
+    template: &str,
+    accept: &mut dyn FnMut(&Proposal) -> bool,
+) -> io::Result<Option<Rewrite>> {
+    let original = String::from_utf8(fs::read(path)?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not valid UTF-8, left unchanged"))?;
+    let template = template.replace('\n', line_ending(&original));
+    let all_lines: Vec<&str> = original.split_inclusive('\n').collect();
+
+    let mut replaced = String::with_capacity(original.len());
+    let mut changes = Vec::new();
+    let mut occurrences = 0;
+    let mut expanded = String::new();
+    for (index, &line) in all_lines.iter().enumerate() {
+        let Some(reported) = spans.get(&(index + 1)) else {
+            replaced.push_str(line);
+            continue;
+        };
+        // Reported spans don't count a byte order mark.
+        let bom = if index == 0 && line.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+        let (content, ending) = line.split_at(line.trim_end_matches('\n').trim_end_matches('\r').len());
+        let before = replaced.len();
+        let mut last = 0;
+        for caps in regex.captures_iter(content) {
+            let m = caps.get(0).expect("group 0 always matches");
+            if !reported.contains(&(m.start().saturating_sub(bom), m.end().saturating_sub(bom))) {
+                continue;
+            }
+            expanded.clear();
+            caps.expand(&template, &mut expanded);
+            let proposal = Proposal { lines: &all_lines, index, span: (m.start(), m.end()), replacement: &expanded };
+            if !accept(&proposal) {
+                continue;
+            }
+            replaced.push_str(&content[last..m.start()]);
+            replaced.push_str(&expanded);
+            last = m.end();
+            occurrences += 1;
+        }
+        replaced.push_str(&content[last..]);
+        let changed = replaced[before..] != *content;
+        replaced.push_str(ending);
+        if changed {
+            changes.push(Change { index, text: replaced[before..].to_string() });
+        }
+    }
+
+    Ok((replaced != original).then(|| Rewrite { path: path.to_string(), original, replaced, changes, occurrences }))
+}
+
+/// `\r\n` if most of the file's lines end that way, otherwise `\n`.
+fn line_ending(text: &str) -> &'static str {
+    let crlf = text.matches("\r\n").count();
+    match crlf > text.matches('\n').count() - crlf {
+        true => "\r\n",
+        false => "\n",
+    }
+}
+
+/// Replaces the file atomically: the new contents go to a temporary file
+/// next to it, which takes the original's permissions and is renamed over
+/// it. With `backup_suffix`, the original is first copied to PATH+SUFFIX.
+/// A symlink is followed, so its target changes and the link stays; a file
+/// with other hard links is left alone, since the rename would split it
+/// from them.
+pub fn write(rewrite: &Rewrite, backup_suffix: Option<&str>) -> io::Result<()> {
+    let path = fs::canonicalize(&rewrite.path)?;
+    let metadata = fs::metadata(&path)?;
+    #[cfg(unix)]
+    if std::os::unix::fs::MetadataExt::nlink(&metadata) > 1 {
+        return Err(io::Error::other("has other hard links, left unchanged"));
+    }
+    let (temp, mut file) = create_temp(&path)?;
+
+    let written = file.write_all(rewrite.replaced.as_bytes()).and_then(|()| {
+        file.set_permissions(metadata.permissions())?;
+        file.sync_all()
+    });
+    drop(file);
+    let replaced = written.and_then(|()| {
+        if let Some(suffix) = backup_suffix {
+            fs::copy(&path, format!("{}{}", rewrite.path, suffix))?;
+        }
+        fs::rename(&temp, &path)
+    });
+    if replaced.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    replaced
+}
+
+/// Creates a new temporary file next to `path`, only readable by its owner
+/// until `write` copies the original's permissions. Names that are already
+/// taken, maybe by a file or symlink someone planted, are skipped rather
+/// than truncated or followed.
+fn create_temp(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path.file_name().map_or_else(Default::default, |name| name.to_string_lossy());
+    loop {
+        let temp = path.with_file_name(format!(
+            ".{}.fsearch-{}-{}",
+            name,
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// An answer to an `--interactive` prompt, as in `git add -p`.
+#[derive(Clone, Copy, PartialEq)]
+enum Answer {
+    Yes,
+    No,
+    /// This and the rest of the file.
+    All,
+    /// Neither this nor anything after it.
+    Quit,
+}
+
+/// Applies `--replace` to each file's results as the search produces them,
+/// printing a line per rewritten file and a total at the end. With
+/// `--diff`, prints a unified diff of each file instead and writes nothing.
+/// With `--interactive`, asks before each replacement.
+pub struct Replacer<'a> {
+    regex: &'a Regex,
+    template: &'a str,
+    backup_suffix: Option<&'a str>,
+    dry_run: bool,
+    confirm: bool,
+    quit: bool,
+    files: usize,
+    lines: usize,
+    occurrences: usize,
+    failed: bool,
+}
+
+impl<'a> Replacer<'a> {
+    pub fn new(regex: &'a Regex, template: &'a str, args: &'a Args) -> Replacer<'a> {
+        Replacer {
+            regex,
+            template,
+            backup_suffix: args.backup_suffix.as_deref(),
+            dry_run: args.diff,
+            confirm: args.interactive,
+            quit: false,
+            files: 0,
+            lines: 0,
+            occurrences: 0,
+            failed: false,
+        }
+    }
+
+    pub fn apply(&mut self, results: &[SearchResult]) {
+        let mut notebooks = BTreeSet::new();
+        for result in results.iter().filter(|result| result.cell.is_some()) {
+            notebooks.insert(result.file_path.as_str());
+        }
+        for (path, spans) in spans_by_file(results) {
+            if self.quit {
+                return;
+            }
+            // Their lines are lines of cells, not of the JSON file.
+            if notebooks.contains(path) {
+                eprintln!("{}", i18n::error(format!("{}: notebook cells can't be rewritten, left unchanged", path)).red());
+                self.failed = true;
+                continue;
+            }
+            let (confirm, quit) = (self.confirm, &mut self.quit);
+            let mut all = false;
+            let mut accept = |proposal: &Proposal| {
+                if !confirm || all {
+                    return true;
+                }
+                if *quit {
+                    return false;
+                }
+                match ask(path, proposal) {
+                    Answer::Yes => true,
+                    Answer::No => false,
+                    Answer::All => {
+                        all = true;
+                        true
+                    }
+                    Answer::Quit => {
+                        *quit = true;
+                        false
+                    }
+                }
+            };
+            let done = match rewrite(path, &spans, self.regex, self.template, &mut accept) {
+                Ok(Some(rewrite)) if !self.dry_run => write(&rewrite, self.backup_suffix).map(|()| Some(rewrite)),
+                other => other,
+            };
+            match done {
+                Ok(Some(rewrite)) if self.dry_run => print!("{}", diff::unified(&rewrite)),
+                Ok(Some(rewrite)) => {
+                    self.files += 1;
+                    self.lines += rewrite.changes.len();
+                    self.occurrences += rewrite.occurrences;
+                    let changed = i18n::tr("replace-file", &[
+                        ("lines", &rewrite.changes.len()),
+                        ("occurrences", &rewrite.occurrences),
+                    ]);
+                    println!("{}  {}", path.themed(theme::palette().path), changed.dimmed());
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("{}", i18n::error(format!("{}: {}", path, e)).red());
+                    self.failed = true;
+                }
+            }
+        }
+    }
+
+    /// Prints the total (diffs stay clean for `patch`); exits with status 1
+    /// if any file couldn't be rewritten.
+    pub fn finish(self) {
+        if !self.dry_run {
+            let total = i18n::tr("replace-summary", &[
+                ("occurrences", &self.occurrences),
+                ("lines", &self.lines),
+                ("files", &self.files),
+            ]);
+            println!("\n{}", total.themed(theme::palette().count));
+        }
+        if self.failed {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The reported match spans of each file's lines. Binary matches have no
+/// lines to rewrite.
+fn spans_by_file(results: &[SearchResult]) -> BTreeMap<&str, LineSpans> {
+    let mut files: BTreeMap<&str, LineSpans> = BTreeMap::new();
+    for result in results.iter().filter(|result| result.byte_offset.is_none()) {
+        let lines = files.entry(&result.file_path).or_default();
+        lines.entry(result.line_number).or_default().extend(&result.matches);
+    }
+    files
+}
+
+/// Lines of context shown above and below a match being confirmed.
+const PROMPT_CONTEXT: usize = 2;
+
+/// Shows the match with the lines around it and the line as it would
+/// become, then reads an answer from stdin. End of input quits.
+fn ask(path: &str, proposal: &Proposal) -> Answer {
+    let lines = proposal.lines;
+    let trim = |line: &str| line.trim_end_matches('\n').trim_end_matches('\r').to_string();
+    let line = trim(lines[proposal.index]);
+    let (start, end) = proposal.span;
+    let location = format!("{}:{}", path, proposal.index + 1);
+    eprintln!("{}", location.themed(theme::palette().path));
+    let first = proposal.index.saturating_sub(PROMPT_CONTEXT);
+    for context in &lines[first..proposal.index] {
+        eprintln!(" {}", trim(context).dimmed());
+    }
+    let matched = theme::palette().paint_match(line[start..end].normal());
+    eprintln!("{}{}{}{}", "-".red(), &line[..start], matched, &line[end..]);
+    eprintln!("{}{}{}{}", "+".green(), &line[..start], proposal.replacement.green(), &line[end..]);
+    for context in lines.iter().skip(proposal.index + 1).take(PROMPT_CONTEXT) {
+        eprintln!(" {}", trim(context).dimmed());
+    }
+
+    let stdin = io::stdin();
+    loop {
+        eprint!("{}", "Replace this match [y,n,a,q]? ".bold());
+        let mut reply = String::new();
+        if stdin.lock().read_line(&mut reply).unwrap_or(0) == 0 {
+            eprintln!();
+            return Answer::Quit;
+        }
+        match reply.trim() {
+            "y" => return Answer::Yes,
+            "n" => return Answer::No,
+            "a" => return Answer::All,
+            "q" => return Answer::Quit,
+            _ => eprintln!("y - replace this match\nn - keep it\na - replace it and the rest in this file\nq - stop; keep the rest"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern;
+    use crate::profile::{FileProfile, Profiler};
+    use clap::Parser;
+
+    /// `content` after searching a file `name` with `flags` and replacing
+    /// what was found with "bar".
+    fn replaced(name: &str, content: &str, flags: &[&str]) -> String {
+        let path = std::env::temp_dir().join(format!("fsearch-test-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        let path = path.to_str().unwrap();
+        let args = Args::parse_from(["fsearch", "--replace", "bar"].iter().chain(flags));
+        let pattern = pattern::compile(&args).unwrap();
+        let results = crate::search_in_file(path, &pattern, &args, &Profiler::new(false), &mut FileProfile::default()).unwrap();
+        let spans = spans_by_file(&results).remove(path).unwrap_or_default();
+        let rewrite = rewrite(path, &spans, pattern.regex(), "bar", &mut |_| true).unwrap();
+        fs::remove_file(path).unwrap();
+        rewrite.map_or_else(|| content.to_string(), |rewrite| rewrite.replaced)
+    }
+
+    #[test]
+    fn scope_limits_what_is_replaced() {
+        let content = "let foo = 1; // foo\nfoo();\n";
+        assert_eq!(replaced("scope.rs", content, &["-t", "foo", "--scope", "comments"]), "let foo = 1; // bar\nfoo();\n");
+        assert_eq!(replaced("all.rs", content, &["-t", "foo"]), "let bar = 1; // bar\nbar();\n");
+    }
+
+    #[test]
+    fn select_leaves_attributes_alone() {
+        let content = "<p class=\"foo\">foo</p>\n<div>foo</div>\n";
+        let expected = "<p class=\"foo\">bar</p>\n<div>foo</div>\n";
+        assert_eq!(replaced("select.html", content, &["-t", "foo", "--select", "p"]), expected);
+    }
+
+    #[test]
+    fn line_breaks_follow_the_file() {
+        let path = std::env::temp_dir().join(format!("fsearch-test-{}-crlf.txt", std::process::id()));
+        fs::write(&path, "foo\r\nbar foo\r\nfoo").unwrap();
+        let path = path.to_str().unwrap();
+        let spans = LineSpans::from([(1, vec![(0, 3)]), (2, vec![(4, 7)]), (3, vec![(0, 3)])]);
+        let regex = Regex::new("foo").unwrap();
+        let rewrite = rewrite(path, &spans, &regex, "a\nb", &mut |_| true).unwrap().unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(rewrite.replaced, "a\r\nb\r\nbar a\r\nb\r\na\r\nb");
+        assert_eq!(rewrite.occurrences, 3);
+
+        assert_eq!(line_ending("a\r\nb\r\nc\n"), "\r\n");
+        assert_eq!(line_ending("a\r\nb\n"), "\n");
+        assert_eq!(line_ending("no newline"), "\n");
+    }
+
+    /// A rewrite of the file at `path` to "new\n".
+    fn to_new(path: &Path) -> Rewrite {
+        let path = path.to_str().unwrap().to_string();
+        Rewrite { original: fs::read_to_string(&path).unwrap(), path, replaced: "new\n".to_string(), changes: Vec::new(), occurrences: 1 }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_keeps_permissions_and_links() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("fsearch-test-{}-write", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target.txt");
+        fs::write(&target, "old\n").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+
+        // Through a symlink the target changes and the link stays a link.
+        let link = dir.join("link.txt");
+        symlink(&target, &link).unwrap();
+        write(&to_new(&link), None).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o640);
+
+        // A file with another hard link is refused.
+        let hard = dir.join("hard.txt");
+        fs::hard_link(&target, &hard).unwrap();
+        assert!(write(&to_new(&hard), None).is_err());
+        fs::remove_file(&hard).unwrap();
+
+        // A planted file at the next temporary name is neither truncated
+        // nor renamed over the target.
+        let next = NEXT_TEMP.load(Ordering::Relaxed);
+        let planted = dir.join(format!(".target.txt.fsearch-{}-{}", std::process::id(), next));
+        fs::write(&planted, "planted").unwrap();
+        fs::write(&target, "old\n").unwrap();
+        write(&to_new(&target), None).unwrap();
+        assert_eq!(fs::read_to_string(&planted).unwrap(), "planted");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
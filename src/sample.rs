@@ -0,0 +1,54 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Keeps a uniform random sample of at most `size` items from a stream of
+/// unknown length (reservoir sampling), remembering how many were offered.
+pub struct Reservoir<T> {
+    size: usize,
+    seen: usize,
+    items: Vec<(usize, T)>,
+    rng: XorShift,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(size: usize) -> Reservoir<T> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        let seed = nanos ^ (u64::from(std::process::id()) << 32);
+        Reservoir { size, seen: 0, items: Vec::with_capacity(size.min(4096)), rng: XorShift(seed | 1) }
+    }
+
+    pub fn offer(&mut self, item: T) {
+        let index = self.seen;
+        self.seen += 1;
+        if self.items.len() < self.size {
+            self.items.push((index, item));
+        } else {
+            let slot = (self.rng.next() % self.seen as u64) as usize;
+            if slot < self.size {
+                self.items[slot] = (index, item);
+            }
+        }
+    }
+
+    /// How many items were offered.
+    pub fn offered(&self) -> usize {
+        self.seen
+    }
+
+    /// The sampled items, in the order they were offered.
+    pub fn into_sorted(mut self) -> Vec<T> {
+        self.items.sort_unstable_by_key(|&(index, _)| index);
+        self.items.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// xorshift64*: plenty for sampling, and needs no extra dependency.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
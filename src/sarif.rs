@@ -0,0 +1,105 @@
+use crate::json;
+use crate::text;
+use crate::SearchResult;
+
+/// Closes the document opened by `open`.
+pub const CLOSE: &str = "]}]}";
+
+/// The rule every result is reported under: a readable slug of the
+/// pattern plus a hash, so different patterns never share an id.
+pub fn rule_id(pattern: &str) -> String {
+    let mut slug = String::new();
+    for c in pattern.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+        if slug.len() >= 40 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    let slug = if slug.is_empty() { "pattern" } else { slug };
+    format!("fsearch/{}-{:08x}", slug, fnv1a(pattern.as_bytes()))
+}
+
+/// Opens a SARIF 2.1.0 log with one run and one rule per pattern, leaving
+/// its results array open.
+pub fn open(patterns: &[String]) -> String {
+    let rules: Vec<String> = patterns
+        .iter()
+        .map(|pattern| {
+            let description = json::string(&format!("Matches of the pattern '{}'", pattern));
+            format!("{{\"id\":{},\"shortDescription\":{{\"text\":{}}}}}", json::string(&rule_id(pattern)), description)
+        })
+        .collect();
+    format!(
+        concat!(
+            "{{\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\"version\":\"2.1.0\",",
+            "\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"fsearch\",\"version\":{},",
+            "\"rules\":[{}]}}}},",
+            "\"columnKind\":\"unicodeCodePoints\",\"results\":["
+        ),
+        json::string(env!("CARGO_PKG_VERSION")),
+        rules.join(",")
+    )
+}
+
+/// Encodes match `index` of `result` as a SARIF result with a physical
+/// location, under the rule of the pattern that matched. Lines and columns
+/// are one-based; columns count code points.
+pub fn result(result: &SearchResult, index: usize, patterns: &[String]) -> String {
+    let pattern = &patterns[result.terms.get(index).copied().unwrap_or(0)];
+    let (start, end) = result.spans()[index];
+    let (start, end, char_start, char_end) = text::char_span(&result.line, start, end);
+    let message = match result.matches.is_empty() {
+        true => format!("line doesn't match the pattern '{}'", pattern),
+        false => format!("'{}' matches the pattern '{}'", &result.line[start..end], pattern),
+    };
+    format!(
+        concat!(
+            "{{\"ruleId\":{},\"level\":\"warning\",\"message\":{{\"text\":{}}},",
+            "\"locations\":[{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{}}},",
+            "\"region\":{{\"startLine\":{},\"startColumn\":{},\"endColumn\":{},\"snippet\":{{\"text\":{}}}}}}}}}]}}"
+        ),
+        json::string(&rule_id(pattern)),
+        json::string(&message),
+        json::string(&uri(&result.file_path)),
+        result.line_number,
+        char_start + 1,
+        char_end + 1,
+        json::string(&result.line)
+    )
+}
+
+/// Turns a file path into a URI reference: relative paths stay relative
+/// (so CI tools resolve them against the checkout), absolute ones become
+/// `file://` URIs.
+fn uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = path.strip_prefix("./").unwrap_or(&path);
+    let mut encoded = String::new();
+    for &b in path.as_bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            encoded.push(b as char);
+        } else if b == b':' && encoded.len() == 1 {
+            // Keep Windows drive letters readable: C:/...
+            encoded.push(':');
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    if path.starts_with('/') {
+        format!("file://{}", encoded)
+    } else if encoded.as_bytes().get(1) == Some(&b':') {
+        format!("file:///{}", encoded)
+    } else {
+        encoded
+    }
+}
+
+/// 32-bit FNV-1a, a stable hash for rule ids.
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
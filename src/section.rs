@@ -0,0 +1,48 @@
+/// Follows the INI/TOML section each line of a file belongs to, for
+/// `--section`. Lines before the first header belong to no section, and
+/// header lines to none of their own.
+pub struct Sections<'a> {
+    wanted: &'a [String],
+    inside: bool,
+}
+
+impl<'a> Sections<'a> {
+    pub fn new(wanted: &'a [String]) -> Sections<'a> {
+        Sections { wanted, inside: false }
+    }
+
+    /// Whether `line`, the next line of the file, is in one of the wanted
+    /// sections.
+    pub fn contains(&mut self, line: &str) -> bool {
+        match header(line) {
+            Some(name) => {
+                self.inside = self.wanted.iter().any(|wanted| wanted == name);
+                false
+            }
+            None => self.inside,
+        }
+    }
+}
+
+/// The name in a `[name]` or TOML `[[name]]` header line, which may be
+/// indented and followed by a `#` or `;` comment.
+fn header(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let rest = line.strip_prefix('[')?;
+    let close = rest.rfind(']')?;
+    let after = rest[close + 1..].trim_start();
+    if !(after.is_empty() || after.starts_with('#') || after.starts_with(';')) {
+        return None;
+    }
+    let name = &rest[..close];
+    Some(name.strip_prefix('[').and_then(|name| name.strip_suffix(']')).unwrap_or(name).trim())
+}
+
+/// Parses a `--section` name, given with or without its brackets.
+pub fn parse_section(spec: &str) -> Result<String, String> {
+    let name = header(spec).unwrap_or(spec.trim());
+    match name.is_empty() {
+        true => Err("section name is empty".to_string()),
+        false => Ok(name.to_string()),
+    }
+}
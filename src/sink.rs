@@ -0,0 +1,215 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::json;
+use crate::SearchResult;
+
+/// How long a webhook may take to accept the connection and answer.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Somewhere match events are pushed besides the normal output. Events are
+/// the objects `--format jsonl` prints, one per match, except that
+/// `--files-without-match` sends one `{"type":"file_without_match","path":…}`
+/// per file it lists.
+#[derive(Debug, Clone)]
+pub enum Sink {
+    /// The system logger, one message per match.
+    Syslog,
+    /// An HTTP endpoint receiving all events of a run in one POST, as
+    /// newline-delimited JSON.
+    Webhook { host: String, port: u16, path: String },
+}
+
+/// Parses `syslog` or `webhook:http://HOST[:PORT]/PATH`, where HOST may be
+/// a bracketed IPv6 address. There is no TLS client, so no `https://`.
+pub fn parse_sink(spec: &str) -> Result<Sink, String> {
+    if spec == "syslog" {
+        return match cfg!(unix) {
+            true => Ok(Sink::Syslog),
+            false => Err("syslog is only available on Unix".to_string()),
+        };
+    }
+    let Some(url) = spec.strip_prefix("webhook:") else {
+        return Err(format!("expected 'syslog' or 'webhook:URL', got '{}'", spec));
+    };
+    let Some(rest) = url.strip_prefix("http://") else {
+        return Err(format!("only http:// webhooks are supported, got '{}'", url));
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    // An IPv6 address is bracketed, as in `[::1]:8080`.
+    let (host, port) = match authority.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((host, "")) => (host, None),
+        Some((host, rest)) => (host, Some(rest.strip_prefix(':').ok_or_else(|| format!("invalid port in '{}'", url))?)),
+        None if authority.starts_with('[') => return Err(format!("unclosed '[' in '{}'", url)),
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some("") | None => 80,
+        Some(port) => port.parse().map_err(|_| format!("invalid port in '{}'", url))?,
+    };
+    if host.is_empty() {
+        return Err(format!("missing host in '{}'", url));
+    }
+    Ok(Sink::Webhook { host: host.to_string(), port, path: path.to_string() })
+}
+
+/// Delivers results to the `--sink`s of a run.
+pub struct Sinks<'a> {
+    sinks: &'a [Sink],
+    /// Events waiting for the webhooks, sent by `flush`.
+    batch: Vec<String>,
+}
+
+impl<'a> Sinks<'a> {
+    pub fn new(sinks: &'a [Sink]) -> Sinks<'a> {
+        Sinks { sinks, batch: Vec::new() }
+    }
+
+    pub fn push(&mut self, result: &SearchResult) {
+        if self.sinks.is_empty() {
+            return;
+        }
+        for index in 0..result.spans().len() {
+            self.send(json::single_match(result, index));
+        }
+    }
+
+    /// Sends the event for a file `--files-without-match` lists.
+    pub fn push_file(&mut self, path: &str) {
+        if !self.sinks.is_empty() {
+            self.send(format!("{{\"type\":\"file_without_match\",\"path\":{}}}", json::string(path)));
+        }
+    }
+
+    fn send(&mut self, event: String) {
+        if self.sinks.iter().any(|sink| matches!(sink, Sink::Syslog)) {
+            syslog(&event);
+        }
+        if self.sinks.iter().any(|sink| matches!(sink, Sink::Webhook { .. })) {
+            self.batch.push(event);
+        }
+    }
+
+    /// Sends the collected events to every webhook. Runs without matches
+    /// send nothing.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let mut body = self.batch.join("\n");
+        body.push('\n');
+        self.batch.clear();
+        for sink in self.sinks {
+            if let Sink::Webhook { host, port, path } = sink {
+                post(host, *port, path, &body).map_err(|e| format!("webhook http://{}{}: {}", authority(host, *port), path, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `host` and `port` as written in a URL or `Host` header: IPv6 addresses
+/// bracketed and the default port left out.
+fn authority(host: &str, port: u16) -> String {
+    let host = match host.contains(':') {
+        true => format!("[{}]", host),
+        false => host.to_string(),
+    };
+    match port {
+        80 => host,
+        port => format!("{}:{}", host, port),
+    }
+}
+
+fn post(host: &str, port: u16, path: &str, body: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(WEBHOOK_TIMEOUT))?;
+    stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: fsearch/{}\r\nContent-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        authority(host, port),
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+        body
+    )?;
+
+    let mut response = Vec::new();
+    stream.take(1024).read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+    let status = response.split_whitespace().nth(1).unwrap_or_default();
+    if !status.starts_with('2') {
+        let line = response.lines().next().unwrap_or("no response");
+        return Err(io::Error::other(format!("server answered '{}'", line)));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn syslog(message: &str) {
+    use std::ffi::CString;
+    use std::sync::Once;
+
+    static OPEN: Once = Once::new();
+    OPEN.call_once(|| unsafe { libc::openlog(c"fsearch".as_ptr(), libc::LOG_PID, libc::LOG_USER) });
+    if let Ok(message) = CString::new(message) {
+        unsafe { libc::syslog(libc::LOG_NOTICE, c"%s".as_ptr(), message.as_ptr()) };
+    }
+}
+
+#[cfg(not(unix))]
+fn syslog(_message: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(spec: &str) -> (String, u16, String) {
+        match parse_sink(spec) {
+            Ok(Sink::Webhook { host, port, path }) => (host, port, path),
+            other => panic!("not a webhook: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn webhook_hosts_ports_and_paths() {
+        assert_eq!(webhook("webhook:http://example.com"), ("example.com".to_string(), 80, "/".to_string()));
+        assert_eq!(webhook("webhook:http://10.0.0.1:8080/hook?x=1"), ("10.0.0.1".to_string(), 8080, "/hook?x=1".to_string()));
+        assert_eq!(webhook("webhook:http://[::1]:9000/in"), ("::1".to_string(), 9000, "/in".to_string()));
+        assert_eq!(webhook("webhook:http://[fe80::1]/"), ("fe80::1".to_string(), 80, "/".to_string()));
+    }
+
+    #[test]
+    fn rejects_what_the_client_cannot_reach() {
+        assert!(parse_sink("webhook:https://example.com/").is_err());
+        assert!(parse_sink("webhook:http://[::1/").is_err());
+        assert!(parse_sink("webhook:http://[::1]x/").is_err());
+        assert!(parse_sink("webhook:http://host:port/").is_err());
+        assert!(parse_sink("webhook:http:///path").is_err());
+    }
+
+    #[test]
+    fn files_without_match_are_file_events() {
+        let webhook = [parse_sink("webhook:http://localhost/").unwrap()];
+        let mut sinks = Sinks::new(&webhook);
+        sinks.push_file("dir/a \"b\".txt");
+        assert_eq!(sinks.batch, [r#"{"type":"file_without_match","path":"dir/a \"b\".txt"}"#]);
+        let mut none = Sinks::new(&[]);
+        none.push_file("a.txt");
+        assert!(none.batch.is_empty());
+    }
+
+    #[test]
+    fn authorities_bracket_ipv6() {
+        assert_eq!(authority("::1", 9000), "[::1]:9000");
+        assert_eq!(authority("example.com", 80), "example.com");
+    }
+}
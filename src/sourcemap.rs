@@ -0,0 +1,256 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::json::{self, Value};
+use crate::SearchResult;
+
+/// One mapped position of a generated line: from `column` on, the text comes
+/// from `line`:`column` of source `source` (all zero-based).
+struct Segment {
+    generated_column: u32,
+    source: u32,
+    line: u32,
+    column: u32,
+}
+
+/// The parts of a version 3 source map a lookup needs.
+struct SourceMap {
+    sources: Vec<String>,
+    /// Mapped segments of each generated line, by column.
+    lines: Vec<Vec<Segment>>,
+}
+
+/// Sets the origin of each result in the script or stylesheet at `path` to
+/// the original source location of its first match, when the file has a
+/// source map: one named by a `sourceMappingURL` comment (a file next to it
+/// or an inline `data:` URL), or else `path.map`. Results that already have
+/// an origin, or no match, are left alone, as are files without a usable map.
+pub fn resolve(path: &str, results: &mut [SearchResult]) {
+    if results.iter().all(|r| r.matches.is_empty() || r.origin.is_some()) {
+        return;
+    }
+    let Some(map) = load(Path::new(path)) else { return };
+    for result in results.iter_mut().filter(|r| r.origin.is_none()) {
+        let Some(&(start, _)) = result.matches.first() else { continue };
+        // Source map columns count UTF-16 code units.
+        let column = result.line[..start].encode_utf16().count() as u32;
+        if let Some((source, line, column)) = map.lookup(result.line_number - 1, column) {
+            result.origin = Some(format!("{}:{}:{}", source, line + 1, column + 1));
+        }
+    }
+}
+
+fn load(path: &Path) -> Option<SourceMap> {
+    let content = fs::read_to_string(path).ok()?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let (text, map_dir) = match mapping_url(&content) {
+        Some(url) if url.starts_with("data:") => (inline(url)?, dir.to_path_buf()),
+        Some(url) if !url.contains("://") => {
+            let map_path = dir.join(url.split(['?', '#']).next().unwrap_or(url));
+            let map_dir = map_path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (fs::read_to_string(&map_path).ok()?, map_dir)
+        }
+        Some(_) => return None,
+        None => {
+            let mut adjacent = path.as_os_str().to_owned();
+            adjacent.push(".map");
+            (fs::read_to_string(adjacent).ok()?, dir.to_path_buf())
+        }
+    };
+    SourceMap::parse(&text, &map_dir)
+}
+
+/// The URL of the last `//# sourceMappingURL=` (or `/*# ... */`) comment.
+fn mapping_url(content: &str) -> Option<&str> {
+    for line in content.lines().rev().take(5) {
+        let line = line.trim();
+        let comment = ["//# ", "//@ ", "/*# ", "/*@ "].iter().find_map(|prefix| line.strip_prefix(prefix));
+        if let Some(url) = comment.and_then(|rest| rest.strip_prefix("sourceMappingURL=")) {
+            let url = url.strip_suffix("*/").unwrap_or(url).trim();
+            return Some(url).filter(|url| !url.is_empty());
+        }
+    }
+    None
+}
+
+/// The JSON of a `data:application/json;base64,...` URL.
+fn inline(url: &str) -> Option<String> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    if !header.starts_with("application/json") {
+        return None;
+    }
+    match header.ends_with(";base64") {
+        true => String::from_utf8(base64(data)?).ok(),
+        false => Some(data.to_string()),
+    }
+}
+
+fn base64(data: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in data.trim_end_matches('=').bytes() {
+        bits = bits << 6 | base64_digit(c)?;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn base64_digit(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+impl SourceMap {
+    /// Parses a source map whose relative sources are relative to `dir`.
+    /// Index maps (with `sections`) are not supported.
+    fn parse(text: &str, dir: &Path) -> Option<SourceMap> {
+        let map = json::parse(text).ok()?;
+        let root = match map.get("sourceRoot") {
+            Some(Value::Str(root)) => root.as_str(),
+            _ => "",
+        };
+        let Some(Value::Array(sources)) = map.get("sources") else { return None };
+        let sources = sources
+            .iter()
+            .map(|source| match source {
+                Value::Str(source) => source_path(dir, root, source),
+                _ => String::new(),
+            })
+            .collect();
+        let Some(Value::Str(mappings)) = map.get("mappings") else { return None };
+        Some(SourceMap { sources, lines: decode(mappings)? })
+    }
+
+    /// The source, line and column that generated `line`:`column`, all
+    /// zero-based.
+    fn lookup(&self, line: usize, column: u32) -> Option<(&str, u32, u32)> {
+        let segments = self.lines.get(line)?;
+        let index = segments.partition_point(|segment| segment.generated_column <= column).checked_sub(1)?;
+        let segment = &segments[index];
+        Some((self.sources.get(segment.source as usize)?, segment.line, segment.column))
+    }
+}
+
+/// Where to report `source`: as given when it's a URL (such as
+/// `webpack://`), else resolved against the map's directory.
+fn source_path(dir: &Path, root: &str, source: &str) -> String {
+    let joined = match root.is_empty() || source.contains("://") {
+        true => source.to_string(),
+        false => format!("{}/{}", root.trim_end_matches('/'), source),
+    };
+    if joined.contains("://") || Path::new(&joined).is_absolute() {
+        return joined;
+    }
+    let mut path = PathBuf::new();
+    for component in dir.join(&joined).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(path.components().next_back(), Some(Component::Normal(_))) => {
+                path.pop();
+            }
+            other => path.push(other),
+        }
+    }
+    path.to_string_lossy().into_owned()
+}
+
+/// Decodes the base64 VLQ `mappings` of a source map into the mapped
+/// segments of each generated line.
+fn decode(mappings: &str) -> Option<Vec<Vec<Segment>>> {
+    let (mut source, mut line, mut column) = (0i64, 0i64, 0i64);
+    let mut lines = Vec::new();
+    for generated in mappings.split(';') {
+        let mut segments = Vec::new();
+        let mut generated_column = 0i64;
+        for segment in generated.split(',').filter(|segment| !segment.is_empty()) {
+            let fields = vlq(segment)?;
+            generated_column += *fields.first()?;
+            if fields.len() >= 4 {
+                source += fields[1];
+                line += fields[2];
+                column += fields[3];
+                segments.push(Segment {
+                    generated_column: u32::try_from(generated_column).ok()?,
+                    source: u32::try_from(source).ok()?,
+                    line: u32::try_from(line).ok()?,
+                    column: u32::try_from(column).ok()?,
+                });
+            }
+        }
+        segments.sort_by_key(|segment| segment.generated_column);
+        lines.push(segments);
+    }
+    Some(lines)
+}
+
+/// The signed values of one base64 VLQ segment.
+fn vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0i64, 0);
+    for c in segment.bytes() {
+        let digit = base64_digit(c)? as i64;
+        value |= (digit & 0x1F) << shift;
+        if digit & 0x20 != 0 {
+            shift += 5;
+            if shift > 60 {
+                return None;
+            }
+            continue;
+        }
+        values.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+        value = 0;
+        shift = 0;
+    }
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vlq_values_are_signed_and_continue() {
+        assert_eq!(vlq("AAAA"), Some(vec![0, 0, 0, 0]));
+        assert_eq!(vlq("CD"), Some(vec![1, -1]));
+        assert_eq!(vlq("gBhB"), Some(vec![16, -16]));
+        assert_eq!(vlq("+/D"), Some(vec![2047]));
+        assert_eq!(vlq("A!"), None);
+    }
+
+    #[test]
+    fn decode_accumulates_across_lines() {
+        let map = SourceMap { sources: vec!["a.ts".to_string(), "b.ts".to_string()], lines: decode("AAAA;;ECAC,EAAE").unwrap() };
+        assert_eq!(map.lines.len(), 3);
+        assert!(map.lines[1].is_empty());
+        assert_eq!(map.lookup(0, 7), Some(("a.ts", 0, 0)));
+        assert_eq!(map.lookup(2, 3), Some(("b.ts", 0, 1)));
+        assert_eq!(map.lookup(2, 4), Some(("b.ts", 0, 3)));
+        assert_eq!(map.lookup(2, 1), None);
+        assert_eq!(map.lookup(1, 0), None);
+    }
+
+    #[test]
+    fn base64_standard_and_url_safe() {
+        assert_eq!(base64("aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(base64("aGk"), Some(b"hi".to_vec()));
+        assert_eq!(base64("_-8"), Some(vec![0xff, 0xef]));
+        assert_eq!(base64("a b"), None);
+    }
+
+    #[test]
+    fn sources_resolve_against_the_map() {
+        assert_eq!(source_path(Path::new("dist"), "", "../src/a.ts"), "src/a.ts");
+        assert_eq!(source_path(Path::new("dist"), "lib/", "./a.ts"), "dist/lib/a.ts");
+        assert_eq!(source_path(Path::new("dist"), "lib", "webpack://app/a.ts"), "webpack://app/a.ts");
+    }
+}
@@ -0,0 +1,336 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use colored::Colorize;
+
+use crate::i18n;
+use crate::aggregate::{SortKey, Sorter};
+use crate::SearchResult;
+
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// Buffers results for modes that need the complete set (sorting,
+/// aggregation). Once more than `limit` results are held, they are written
+/// to a temporary run file — sorted first when a `Sorter` is given — so peak
+/// memory stays bounded; reading back merges the runs.
+pub struct ResultBuffer<'a> {
+    memory: Vec<SearchResult>,
+    runs: Vec<Run>,
+    len: usize,
+    limit: usize,
+    sorter: Option<&'a Sorter<'a>>,
+}
+
+impl<'a> ResultBuffer<'a> {
+    pub fn new(limit: usize, sorter: Option<&'a Sorter<'a>>) -> ResultBuffer<'a> {
+        ResultBuffer {
+            memory: Vec::new(),
+            runs: Vec::new(),
+            len: 0,
+            limit: limit.max(1),
+            sorter,
+        }
+    }
+
+    /// Number of results buffered so far, in memory or on disk.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn extend(&mut self, results: Vec<SearchResult>) -> io::Result<()> {
+        self.len += results.len();
+        self.memory.extend(results);
+        if self.memory.len() >= self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        if let Some(sorter) = self.sorter {
+            sorter.sort(&mut self.memory);
+        }
+        let (run, file) = Run::create()?;
+        let mut writer = BufWriter::new(file);
+        for result in self.memory.drain(..) {
+            write_result(&mut writer, &result)?;
+        }
+        writer.flush()?;
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Returns every buffered result, in sorted order when a `Sorter` was
+    /// given and in arrival order otherwise.
+    pub fn into_results(mut self) -> io::Result<Box<dyn Iterator<Item = SearchResult> + 'a>> {
+        if let Some(sorter) = self.sorter {
+            sorter.sort(&mut self.memory);
+        }
+        if self.runs.is_empty() {
+            return Ok(Box::new(self.memory.into_iter()));
+        }
+
+        let mut sources: Vec<Box<dyn Iterator<Item = SearchResult>>> = Vec::new();
+        for run in self.runs {
+            sources.push(Box::new(RunReader::open(run)?));
+        }
+        sources.push(Box::new(self.memory.into_iter()));
+
+        Ok(match self.sorter {
+            Some(sorter) => Box::new(Merge::new(sources, sorter)),
+            None => Box::new(sources.into_iter().flatten()),
+        })
+    }
+}
+
+/// A temporary file holding one spilled run; removed when dropped.
+struct Run {
+    path: PathBuf,
+}
+
+impl Run {
+    /// Creates a new run file, readable only by us. The name is predictable,
+    /// so the file must not exist yet: an existing file (or a symlink planted
+    /// in a shared temporary directory) is skipped, never truncated.
+    fn create() -> io::Result<(Run, fs::File)> {
+        loop {
+            let name = format!(
+                "fsearch-spill-{}-{}",
+                std::process::id(),
+                NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+            );
+            let path = std::env::temp_dir().join(name);
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(file) => return Ok((Run { path }, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+struct RunReader {
+    reader: BufReader<fs::File>,
+    // Keeps the file alive until the reader is done with it.
+    run: Run,
+}
+
+impl RunReader {
+    fn open(run: Run) -> io::Result<RunReader> {
+        let reader = BufReader::new(fs::File::open(&run.path)?);
+        Ok(RunReader { reader, run })
+    }
+}
+
+impl Iterator for RunReader {
+    type Item = SearchResult;
+
+    fn next(&mut self) -> Option<SearchResult> {
+        match read_result(&mut self.reader) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    i18n::error(format!("reading spill file {}: {}", self.run.path.display(), e)).red()
+                );
+                None
+            }
+        }
+    }
+}
+
+/// K-way merge of sorted sources. Ties go to the earlier source, which keeps
+/// the overall sort stable since runs are spilled in arrival order.
+struct Merge<'a> {
+    sources: Vec<Box<dyn Iterator<Item = SearchResult>>>,
+    heads: BinaryHeap<Reverse<(SortKey, usize)>>,
+    pending: Vec<Option<SearchResult>>,
+    sorter: &'a Sorter<'a>,
+}
+
+impl<'a> Merge<'a> {
+    fn new(mut sources: Vec<Box<dyn Iterator<Item = SearchResult>>>, sorter: &'a Sorter<'a>) -> Merge<'a> {
+        let mut heads = BinaryHeap::new();
+        let mut pending = Vec::new();
+        for (index, source) in sources.iter_mut().enumerate() {
+            let head = source.next();
+            if let Some(result) = &head {
+                heads.push(Reverse((sorter.key(result), index)));
+            }
+            pending.push(head);
+        }
+        Merge { sources, heads, pending, sorter }
+    }
+}
+
+impl Iterator for Merge<'_> {
+    type Item = SearchResult;
+
+    fn next(&mut self) -> Option<SearchResult> {
+        let Reverse((_, index)) = self.heads.pop()?;
+        let result = self.pending[index].take();
+        if let Some(next) = self.sources[index].next() {
+            self.heads.push(Reverse((self.sorter.key(&next), index)));
+            self.pending[index] = Some(next);
+        }
+        result
+    }
+}
+
+fn write_result(writer: &mut impl Write, result: &SearchResult) -> io::Result<()> {
+    write_str(writer, &result.file_path)?;
+    writer.write_all(&(result.line_number as u64).to_le_bytes())?;
+    write_str(writer, &result.line)?;
+    writer.write_all(&(result.matches.len() as u64).to_le_bytes())?;
+    for &(start, end) in &result.matches {
+        writer.write_all(&(start as u64).to_le_bytes())?;
+        writer.write_all(&(end as u64).to_le_bytes())?;
+    }
+    writer.write_all(&result.byte_offset.map_or(u64::MAX, |offset| offset as u64).to_le_bytes())?;
+    writer.write_all(&(result.terms.len() as u64).to_le_bytes())?;
+    for &term in &result.terms {
+        writer.write_all(&(term as u64).to_le_bytes())?;
+    }
+    // Origins are never empty, so an empty string stands for none.
+    write_str(writer, result.origin.as_deref().unwrap_or_default())?;
+    writer.write_all(&result.cell.map_or(u64::MAX, |cell| cell as u64).to_le_bytes())?;
+    writer.write_all(&(result.context.len() as u64).to_le_bytes())?;
+    for (number, line) in &result.context {
+        writer.write_all(&(*number as u64).to_le_bytes())?;
+        write_str(writer, line)?;
+    }
+    Ok(())
+}
+
+/// Reads one record, or `None` at a clean end of file.
+fn read_result(reader: &mut impl Read) -> io::Result<Option<SearchResult>> {
+    let file_path = match read_u64(reader) {
+        Ok(len) => read_string(reader, len)?,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let line_number = read_u64(reader)? as usize;
+    let len = read_u64(reader)?;
+    let line = read_string(reader, len)?;
+    let count = read_u64(reader)?;
+    let mut matches = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        matches.push((read_u64(reader)? as usize, read_u64(reader)? as usize));
+    }
+    let byte_offset = Some(read_u64(reader)?).filter(|&offset| offset != u64::MAX).map(|offset| offset as usize);
+    let count = read_u64(reader)?;
+    let terms = (0..count).map(|_| read_u64(reader).map(|term| term as usize)).collect::<io::Result<_>>()?;
+    let len = read_u64(reader)?;
+    let origin = Some(read_string(reader, len)?).filter(|origin| !origin.is_empty());
+    let cell = Some(read_u64(reader)?).filter(|&cell| cell != u64::MAX).map(|cell| cell as usize);
+    let count = read_u64(reader)?;
+    let mut context = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let number = read_u64(reader)? as usize;
+        let len = read_u64(reader)?;
+        context.push((number, read_string(reader, len)?));
+    }
+    Ok(Some(SearchResult { file_path, line_number, line, matches, byte_offset, terms, origin, cell, context }))
+}
+
+fn write_str(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    writer.write_all(&(s.len() as u64).to_le_bytes())?;
+    writer.write_all(s.as_bytes())
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string(reader: &mut impl Read, len: u64) -> io::Result<String> {
+    let mut buf = vec![0; len as usize];
+    reader.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn result(file_path: &str, line_number: usize, line: &str) -> SearchResult {
+        SearchResult {
+            file_path: file_path.to_string(),
+            line_number,
+            line: line.to_string(),
+            matches: vec![(0, line.len())],
+            byte_offset: None,
+            terms: vec![0],
+            origin: None,
+            cell: None,
+            context: Vec::new(),
+        }
+    }
+
+    fn locations(results: impl Iterator<Item = SearchResult>) -> Vec<(String, usize)> {
+        results.map(|r| (r.file_path, r.line_number)).collect()
+    }
+
+    #[test]
+    fn runs_round_trip_every_field() {
+        let mut full = result("dir/ä.rs", 7, "let é = 1;");
+        full.byte_offset = Some(42);
+        full.terms = vec![1];
+        full.origin = Some("code cell 2".to_string());
+        full.cell = Some(2);
+        full.context = vec![(6, "before".to_string()), (8, String::new())];
+        let plain = result("b.rs", 1, "");
+
+        let (run, file) = Run::create().unwrap();
+        let mut writer = BufWriter::new(file);
+        write_result(&mut writer, &full).unwrap();
+        write_result(&mut writer, &plain).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let read: Vec<SearchResult> = RunReader::open(run).unwrap().collect();
+        assert_eq!(format!("{:?}", read), format!("{:?}", [full, plain]));
+    }
+
+    #[test]
+    fn merge_orders_results_across_runs() {
+        // Keys that put the results in file and line order, arriving out of it.
+        let pattern = Regex::new(r"k=(\d+)").unwrap();
+        let sorter = Sorter { pattern: &pattern, group: 1, numeric: true };
+        let mut buffer = ResultBuffer::new(2, Some(&sorter));
+        for batch in [[("b.rs", 1, "k=4"), ("a.rs", 1, "k=1")], [("a.rs", 3, "k=3"), ("b.rs", 2, "k=5")], [("a.rs", 2, "k=2"), ("c.rs", 1, "k=6")]] {
+            buffer.extend(batch.iter().map(|&(path, line, text)| result(path, line, text)).collect()).unwrap();
+        }
+        assert_eq!(buffer.runs.len(), 3);
+        let expected = [("a.rs", 1), ("a.rs", 2), ("a.rs", 3), ("b.rs", 1), ("b.rs", 2), ("c.rs", 1)];
+        let expected: Vec<(String, usize)> = expected.iter().map(|&(path, line)| (path.to_string(), line)).collect();
+        assert_eq!(locations(buffer.into_results().unwrap()), expected);
+    }
+
+    #[test]
+    fn existing_files_are_never_reused() {
+        let next = NEXT_RUN.load(Ordering::Relaxed);
+        let planted = std::env::temp_dir().join(format!("fsearch-spill-{}-{}", std::process::id(), next));
+        fs::write(&planted, "planted").unwrap();
+        let (run, _file) = Run::create().unwrap();
+        assert_ne!(run.path, planted);
+        assert_eq!(fs::read_to_string(&planted).unwrap(), "planted");
+        fs::remove_file(&planted).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::pattern::Matcher;
+use crate::{Args, SearchResult};
+
+/// What kind of definition `fsearch symbols` found.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SymbolKind {
+    /// Functions and methods
+    Function,
+    /// Structs, classes, enums, traits, interfaces and type aliases
+    Type,
+    /// Constants, statics and #defines
+    Constant,
+    /// Modules, namespaces and packages
+    Module,
+    /// Macros
+    Macro,
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Type => "type",
+            SymbolKind::Constant => "constant",
+            SymbolKind::Module => "module",
+            SymbolKind::Macro => "macro",
+        };
+        f.write_str(name)
+    }
+}
+
+use SymbolKind::*;
+
+/// How to spot definitions in one language, ctags-style: one pattern per
+/// definition form, each capturing the symbol as `name`. The first pattern
+/// that matches a line decides what it defines.
+struct Language {
+    extensions: &'static [&'static str],
+    definitions: &'static [(SymbolKind, &'static str)],
+}
+
+const VISIBILITY: &str = r"(?:pub(?:\([^)]*\))?\s+)?";
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        definitions: &[
+            (Function, r#"^\s*{vis}(?:default\s+)?(?:(?:const|async|unsafe|extern(?:\s+"[^"]*")?)\s+)*fn\s+(?P<name>\w+)"#),
+            (Type, r"^\s*{vis}(?:unsafe\s+)?(?:struct|enum|union|trait|type)\s+(?P<name>\w+)"),
+            (Constant, r"^\s*{vis}(?:const|static(?:\s+mut)?)\s+(?P<name>\w+)\s*:"),
+            (Module, r"^\s*{vis}mod\s+(?P<name>\w+)"),
+            (Macro, r"^\s*macro_rules!\s*(?P<name>\w+)"),
+        ],
+    },
+    Language {
+        extensions: &["py", "pyi"],
+        definitions: &[
+            (Function, r"^\s*(?:async\s+)?def\s+(?P<name>\w+)"),
+            (Type, r"^\s*class\s+(?P<name>\w+)"),
+            (Constant, r"^(?P<name>[A-Z][A-Z0-9_]*)\s*(?::[^=]*)?=[^=]"),
+        ],
+    },
+    Language {
+        extensions: &["js", "mjs", "cjs", "jsx", "ts", "tsx", "mts", "cts"],
+        definitions: &[
+            (Function, r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?function\s*\*?\s*(?P<name>[\w$]+)"),
+            (
+                Function,
+                r"^\s*(?:export\s+)?(?:const|let|var)\s+(?P<name>[\w$]+)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function\b|\([^)]*\)\s*(?::[^=]+)?=>|[\w$]+\s*=>)",
+            ),
+            (Type, r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:class|interface|enum|type)\s+(?P<name>[\w$]+)"),
+            (Constant, r"^\s*(?:export\s+)?const\s+(?P<name>[A-Z][A-Z0-9_$]*)\s*(?::[^=]+)?="),
+            (Module, r"^\s*(?:export\s+)?(?:declare\s+)?namespace\s+(?P<name>[\w$.]+)"),
+        ],
+    },
+    Language {
+        extensions: &["go"],
+        definitions: &[
+            (Function, r"^func\s+(?:\([^)]*\)\s*)?(?P<name>\w+)"),
+            (Type, r"^\s*type\s+(?P<name>\w+)"),
+            (Constant, r"^\s*const\s+(?P<name>\w+)"),
+            (Module, r"^package\s+(?P<name>\w+)"),
+        ],
+    },
+    Language {
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        definitions: &[
+            (Constant, r"^\s*#\s*define\s+(?P<name>\w+)"),
+            (Type, r"^\s*(?:typedef\s+)?(?:struct|union|enum(?:\s+class)?|class)\s+(?P<name>\w+)\s*(?:[:{]|$)"),
+            (Type, r"^\s*typedef\b.*?\b(?P<name>\w+)\s*;"),
+            (Module, r"^\s*namespace\s+(?P<name>[\w:]+)"),
+            // A definition starts at the left margin and is not a declaration.
+            (Function, r"^(?:[\w:<>*&~]+[\s*&]+)+(?P<name>[\w:~]+)\s*\([^;]*$"),
+        ],
+    },
+    Language {
+        extensions: &["java"],
+        definitions: &[
+            (Type, r"^\s*(?:(?:public|protected|private|static|final|abstract|sealed)\s+)*(?:class|interface|enum|record|@interface)\s+(?P<name>\w+)"),
+            (Constant, r"^\s*(?:(?:public|protected|private)\s+)?static\s+final\s+[\w<>\[\],]+\s+(?P<name>\w+)\s*="),
+            (
+                Function,
+                r"^\s*(?:(?:public|protected|private|static|final|abstract|synchronized|native|default)\s+)+(?:<[^>]*>\s*)?[\w<>\[\],.?]+\s+(?P<name>\w+)\s*\(",
+            ),
+        ],
+    },
+    Language {
+        extensions: &["cs"],
+        definitions: &[
+            (
+                Type,
+                r"^\s*(?:(?:public|protected|private|internal|static|sealed|abstract|partial|readonly)\s+)*(?:class|interface|enum|struct|record)\s+(?P<name>\w+)",
+            ),
+            (Constant, r"\bconst\s+[\w<>\[\],.?]+\s+(?P<name>\w+)\s*="),
+            (
+                Function,
+                r"^\s*(?:(?:public|protected|private|internal|static|async|override|virtual|abstract|sealed|extern)\s+)+[\w<>\[\],.?]+\s+(?P<name>\w+)\s*[(<]",
+            ),
+            (Module, r"^\s*namespace\s+(?P<name>[\w.]+)"),
+        ],
+    },
+    Language {
+        extensions: &["kt", "kts"],
+        definitions: &[
+            (Function, r"^\s*(?:[a-z]+\s+)*fun\s+(?:<[^>]*>\s*)?(?:[\w.]+\.)?(?P<name>\w+)"),
+            (Constant, r"^\s*(?:[a-z]+\s+)*const\s+val\s+(?P<name>\w+)"),
+            (Type, r"^\s*(?:[a-z]+\s+)*(?:class|interface|object|typealias)\s+(?P<name>\w+)"),
+            (Module, r"^package\s+(?P<name>[\w.]+)"),
+        ],
+    },
+    Language {
+        extensions: &["swift"],
+        definitions: &[
+            (Function, r"^\s*(?:[@\w]+\s+)*func\s+(?P<name>\w+)"),
+            (Type, r"^\s*(?:[@\w]+\s+)*(?:class|struct|enum|protocol|actor|typealias)\s+(?P<name>\w+)"),
+        ],
+    },
+    Language {
+        extensions: &["rb"],
+        definitions: &[
+            (Function, r"^\s*def\s+(?:self\.)?(?P<name>\w+[?!=]?)"),
+            (Type, r"^\s*class\s+(?P<name>[\w:]+)"),
+            (Module, r"^\s*module\s+(?P<name>[\w:]+)"),
+            (Constant, r"^\s*(?P<name>[A-Z][A-Z0-9_]*)\s*=[^=~]"),
+        ],
+    },
+    Language {
+        extensions: &["sh", "bash", "zsh"],
+        definitions: &[
+            (Function, r"^\s*function\s+(?P<name>[\w.:-]+)"),
+            (Function, r"^\s*(?P<name>[\w.:-]+)\s*\(\)"),
+        ],
+    },
+];
+
+/// Control-flow keywords that the looser patterns (C function definitions
+/// in particular) would otherwise take for names.
+const KEYWORDS: &[&str] = &["if", "else", "for", "while", "do", "switch", "return", "catch", "sizeof", "new", "delete"];
+
+/// The compiled definitions of every language, in `LANGUAGES` order.
+fn compiled() -> &'static [Vec<(SymbolKind, Regex)>] {
+    static COMPILED: OnceLock<Vec<Vec<(SymbolKind, Regex)>>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        LANGUAGES
+            .iter()
+            .map(|language| {
+                language
+                    .definitions
+                    .iter()
+                    .map(|&(kind, pattern)| (kind, Regex::new(&pattern.replace("{vis}", VISIBILITY)).unwrap()))
+                    .collect()
+            })
+            .collect()
+    })
+}
+
+/// The definitions for the language of `path`, by its extension.
+pub fn definitions(path: &str) -> Option<&'static [(SymbolKind, Regex)]> {
+    let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    let index = LANGUAGES.iter().position(|language| language.extensions.contains(&ext.as_str()))?;
+    Some(&compiled()[index])
+}
+
+/// Finds the definitions in `content` whose name `pattern` matches,
+/// optionally only those of the `--kind`s asked for. Results highlight the
+/// match in the name and carry the kind of definition as their origin.
+pub fn search(
+    content: &str,
+    file_path: &str,
+    definitions: &[(SymbolKind, Regex)],
+    pattern: &Matcher,
+    args: &Args,
+) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let found = definitions.iter().find_map(|(kind, regex)| {
+            let name = regex.captures(line)?.name("name")?;
+            Some((*kind, name)).filter(|_| !KEYWORDS.contains(&name.as_str()))
+        });
+        let Some((kind, name)) = found else { continue };
+        if !args.kind.is_empty() && !args.kind.contains(&kind) {
+            continue;
+        }
+        let (matches, terms) = pattern.find(name.as_str());
+        if matches.is_empty() {
+            continue;
+        }
+        results.push(SearchResult {
+            file_path: file_path.to_string(),
+            line_number: index + 1,
+            line: line.to_string(),
+            matches: matches.into_iter().map(|(s, e)| (s + name.start(), e + name.start())).collect(),
+            byte_offset: None,
+            terms,
+            origin: Some(kind.to_string()),
+            cell: None,
+            context: Vec::new(),
+        });
+        if args.stop_at_first_match() {
+            break;
+        }
+    }
+    results
+}
@@ -0,0 +1,178 @@
+use std::fs;
+
+use crate::meta::MetaField;
+use crate::text;
+use crate::SearchResult;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Var {
+    Path,
+    Line,
+    Column,
+    Text,
+    Matched,
+    Meta(MetaField),
+}
+
+impl Var {
+    fn from_name(name: &str) -> Option<Var> {
+        match name {
+            "path" => Some(Var::Path),
+            "line" => Some(Var::Line),
+            "column" => Some(Var::Column),
+            "text" => Some(Var::Text),
+            "matched" => Some(Var::Matched),
+            "size" => Some(Var::Meta(MetaField::Size)),
+            "mtime" => Some(Var::Meta(MetaField::Mtime)),
+            "owner" => Some(Var::Meta(MetaField::Owner)),
+            _ => None,
+        }
+    }
+
+    fn needs_metadata(self) -> bool {
+        matches!(self, Var::Meta(_))
+    }
+}
+
+#[derive(Debug)]
+enum Segment {
+    Literal(String),
+    Var(Var),
+}
+
+/// A user-supplied output format such as `{path}\t{line}\t{matched}`.
+///
+/// Recognized variables are `path`, `line`, `column`, `text`, `matched`,
+/// `size`, `mtime` and `owner`. `{{` and `}}` produce literal braces, and the escapes
+/// `\t`, `\n` and `\\` are expanded so templates can be passed from a shell
+/// without `$'...'` quoting.
+#[derive(Debug)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unterminated variable '{{{}'", name)),
+                        }
+                    }
+                    let var = Var::from_name(&name)
+                        .ok_or_else(|| format!("unknown template variable '{{{}}}'", name))?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Var(var));
+                }
+                '}' => return Err("unmatched '}' in template (use '}}' for a literal brace)".to_string()),
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                    None => literal.push('\\'),
+                },
+                _ => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template { segments })
+    }
+
+    /// Whether rendering requires the file's metadata to be looked up.
+    pub fn needs_metadata(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|s| matches!(s, Segment::Var(v) if v.needs_metadata()))
+    }
+
+    /// Renders one output row for the match at `matches[index]` of `result`.
+    pub fn render(&self, result: &SearchResult, index: usize, meta: Option<&fs::Metadata>) -> String {
+        let (start, end) = result.matches.get(index).copied().unwrap_or((0, 0));
+        let mut out = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Var(Var::Path) => out.push_str(&result.file_path),
+                Segment::Var(Var::Line) => out.push_str(&result.line_number.to_string()),
+                Segment::Var(Var::Column) => out.push_str(&(start + 1).to_string()),
+                Segment::Var(Var::Text) => out.push_str(&result.line),
+                Segment::Var(Var::Matched) => {
+                    let (start, end) = text::snap_span(&result.line, start, end);
+                    out.push_str(&result.line[start..end]);
+                }
+                Segment::Var(Var::Meta(field)) => {
+                    if let Some(value) = meta.and_then(|m| field.value(m)) {
+                        out.push_str(&value);
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(line: &str, matches: Vec<(usize, usize)>) -> SearchResult {
+        SearchResult {
+            file_path: "src/a.rs".to_string(),
+            line_number: 12,
+            line: line.to_string(),
+            terms: vec![0; matches.len()],
+            matches,
+            byte_offset: None,
+            origin: None,
+            cell: None,
+            context: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn renders_each_match() {
+        let template = Template::parse(r"{path}:{line}:{column}\t{matched}|{text}").unwrap();
+        let result = result("let foo = foo;", vec![(4, 7), (10, 13)]);
+        assert_eq!(template.render(&result, 0, None), "src/a.rs:12:5\tfoo|let foo = foo;");
+        assert_eq!(template.render(&result, 1, None), "src/a.rs:12:11\tfoo|let foo = foo;");
+        assert!(!template.needs_metadata());
+        assert!(Template::parse("{size}").unwrap().needs_metadata());
+    }
+
+    #[test]
+    fn braces_and_escapes() {
+        let template = Template::parse(r"{{{line}}}\n\\\q").unwrap();
+        assert_eq!(template.render(&result("x", vec![(0, 1)]), 0, None), "{12}\n\\\\q");
+        assert!(Template::parse("{nope}").unwrap_err().contains("unknown template variable '{nope}'"));
+        assert!(Template::parse("{path").is_err());
+        assert!(Template::parse("a}b").is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use clap::ValueEnum;
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal that supports it
+    Auto,
+    /// Always emit colors, even when piped
+    Always,
+    /// Never emit colors
+    Never,
+}
+
+/// Applies an explicit `--color` choice on top of what `init` detected.
+pub fn set_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Auto => return,
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    colored::control::set_override(enabled);
+    console::set_colors_enabled(enabled);
+}
+
+/// Prepares the console for colored UTF-8 output. On Windows this enables
+/// ANSI escape processing (turning colors off on legacy consoles that lack
+/// it) and switches the output code page to UTF-8 so non-ASCII text isn't
+/// garbled in the console or when PowerShell captures redirected output.
+/// Elsewhere terminals already speak ANSI and UTF-8.
+#[cfg(windows)]
+pub fn init() {
+    use std::ffi::c_void;
+
+    type Handle = *mut c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    const CP_UTF8: u32 = 65001;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> Handle;
+        fn GetConsoleMode(console: Handle, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: Handle, mode: u32) -> i32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+
+    // SAFETY: plain Win32 calls on the process's own standard handles.
+    unsafe {
+        SetConsoleOutputCP(CP_UTF8);
+
+        let mut legacy = false;
+        for std_handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+            let handle = GetStdHandle(std_handle);
+            let mut mode = 0;
+            // Not a console (redirected to a file or pipe): nothing to enable,
+            // and colored already skips colors for non-terminals.
+            if GetConsoleMode(handle, &mut mode) == 0 {
+                continue;
+            }
+            if mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING == 0
+                && SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) == 0
+            {
+                legacy = true;
+            }
+        }
+
+        if legacy {
+            colored::control::set_override(false);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn init() {}
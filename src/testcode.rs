@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::{Component, Path};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::highlight::{self, Scope, State};
+use crate::SearchResult;
+
+/// Directories whose contents are test code.
+const TEST_DIRS: &[&str] = &["tests", "test", "__tests__", "spec", "specs"];
+
+/// Languages whose tests live in `#[cfg(test)]` modules and `#[test]`
+/// functions next to the code, rather than in files of their own.
+fn has_test_regions(path: &str) -> bool {
+    path.rsplit_once('.').is_some_and(|(_, ext)| ext.eq_ignore_ascii_case("rs"))
+}
+
+/// Whether the file at `path`, under the search directory `root`, is test
+/// code by its name or location: `*_test.go`, `test_*.py`, `*.spec.ts`,
+/// `FooTest.java`, anything under a `tests/` directory and so on.
+pub fn is_test_file(path: &str, root: &str) -> bool {
+    let path = Path::new(path);
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let in_test_dir = relative.parent().is_some_and(|parent| {
+        parent.components().any(|c| matches!(c, Component::Normal(name) if TEST_DIRS.contains(&&*name.to_string_lossy())))
+    });
+    if in_test_dir {
+        return true;
+    }
+    let Some(name) = relative.file_name().map(|n| n.to_string_lossy()) else { return false };
+    let (stem, ext) = name.split_once('.').unwrap_or((&name, ""));
+    let lower = stem.to_ascii_lowercase();
+    if lower.starts_with("test_") || ["_test", "_tests", "_spec", "_unittest"].iter().any(|s| lower.ends_with(s)) {
+        return true;
+    }
+    // foo.test.ts, foo.spec.js
+    if ext.split('.').any(|part| part == "test" || part == "spec") {
+        return true;
+    }
+    // FooTest.java, FooTests.cs, FooSpec.scala
+    let jvm_like = matches!(ext, "java" | "kt" | "kts" | "scala" | "cs" | "groovy" | "swift");
+    name == "conftest.py" || name == "tests.rs" || (jvm_like && ["Test", "Tests", "Spec", "IT"].iter().any(|s| stem.len() > s.len() && stem.ends_with(s)))
+}
+
+/// Whether `--tests-only` (`keep_tests`) or `--no-tests` can rule out the
+/// file at `path` without reading it.
+pub fn skips_file(path: &str, root: &str, keep_tests: bool) -> bool {
+    let test_file = is_test_file(path, root);
+    match keep_tests {
+        true => !test_file && !has_test_regions(path),
+        false => test_file,
+    }
+}
+
+/// Keeps only the results inside test regions of the file at `path`
+/// (`keep_tests`), or only those outside them. Files without test regions
+/// are settled by `skips_file` and left alone.
+pub fn retain(path: &str, root: &str, results: &mut Vec<SearchResult>, keep_tests: bool) {
+    if results.is_empty() || is_test_file(path, root) || !has_test_regions(path) {
+        return;
+    }
+    let Ok(content) = fs::read_to_string(path) else { return };
+    let in_tests = test_lines(path, &content);
+    results.retain(|r| in_tests.get(r.line_number.wrapping_sub(1)).copied().unwrap_or(false) == keep_tests);
+}
+
+/// Marks the lines of Rust `content` that belong to an item annotated
+/// `#[cfg(test)]` or `#[test]` (or an attribute like `#[tokio::test]`),
+/// from the attribute to the brace that closes the item.
+fn test_lines(path: &str, content: &str) -> Vec<bool> {
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let attribute =
+        ATTRIBUTE.get_or_init(|| Regex::new(r"^\s*#\[(?:cfg\((?:all\()?test\b|(?:\w+::)*test\])").unwrap());
+    let Some(syntax) = highlight::detect(path) else { return Vec::new() };
+
+    let mut state = State::default();
+    let mut lines = Vec::new();
+    // Saw the attribute but not the item's opening brace yet.
+    let mut pending = false;
+    let mut depth = 0usize;
+    for line in content.lines() {
+        let ranges = syntax.scope_ranges(line, &mut state, Scope::Code);
+        if !pending && depth == 0 && attribute.is_match(line) {
+            pending = true;
+        }
+        lines.push(pending || depth > 0);
+        if !pending && depth == 0 {
+            continue;
+        }
+        'code: for (start, end) in ranges {
+            for c in line[start..end].chars() {
+                match c {
+                    '{' => {
+                        depth += 1;
+                        pending = false;
+                    }
+                    '}' if depth > 0 => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break 'code;
+                        }
+                    }
+                    // An item without a body, like `mod tests;`.
+                    ';' if pending => {
+                        pending = false;
+                        break 'code;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    lines
+}
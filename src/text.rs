@@ -0,0 +1,100 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Terminal columns taken by `s`: CJK and most emoji take two, combining
+/// marks none.
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// The longest prefix of `s` that fits in `max` columns, never splitting a
+/// character.
+pub fn truncate_to_width(s: &str, max: usize) -> &str {
+    let mut width = 0;
+    for (index, c) in s.char_indices() {
+        width += c.width().unwrap_or(0);
+        if width > max {
+            return &s[..index];
+        }
+    }
+    s
+}
+
+/// Pads `s` with spaces to `width` columns. `s` may contain ANSI color codes
+/// as long as `visible` is its uncolored text.
+pub fn pad_to_width(s: &str, visible: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(visible));
+    format!("{}{}", s, " ".repeat(padding))
+}
+
+const ELLIPSIS: &str = "…";
+
+/// At most `chars` characters of `line` around its first match, with an
+/// ellipsis on each side that was cut, and `matches` moved to the excerpt.
+/// Matches outside it become empty, so they still line up with their terms;
+/// one longer than the excerpt keeps its start.
+pub fn snippet(line: &str, matches: &[(usize, usize)], chars: usize) -> (String, Vec<(usize, usize)>) {
+    let offsets: Vec<usize> = line.char_indices().map(|(i, _)| i).chain([line.len()]).collect();
+    let count = offsets.len() - 1;
+    if count <= chars {
+        return (line.to_string(), matches.to_vec());
+    }
+    let (_, _, match_start, match_end) = match matches.first() {
+        Some(&(start, end)) => char_span(line, start, end),
+        None => (0, 0, 0, 0),
+    };
+    let padding = chars.saturating_sub(match_end - match_start) / 2;
+    let first = match_start.saturating_sub(padding).min(count - chars);
+    let (start, end) = (offsets[first], offsets[first + chars]);
+
+    let before = if start > 0 { ELLIPSIS } else { "" };
+    let after = if end < line.len() { ELLIPSIS } else { "" };
+    let shift = |offset: usize| offset.clamp(start, end) - start + before.len();
+    let moved = matches
+        .iter()
+        .map(|&(match_start, match_end)| (shift(match_start), shift(match_end)))
+        .collect();
+    (format!("{}{}{}", before, &line[start..end], after), moved)
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Widens a byte span so it starts and ends on character boundaries and
+/// doesn't cut through a grapheme: a start inside a combining sequence moves
+/// back to its base character, and an end absorbs trailing combining marks,
+/// variation selectors and zero-width-joined emoji. Offsets past the end of
+/// `line` are clamped.
+pub fn snap_span(line: &str, start: usize, end: usize) -> (usize, usize) {
+    let mut start = start.min(line.len());
+    let mut end = end.min(line.len()).max(start);
+
+    while !line.is_char_boundary(start) {
+        start -= 1;
+    }
+    while start > 0 && continues_grapheme(line, start) {
+        start = line[..start].char_indices().next_back().map_or(0, |(i, _)| i);
+    }
+
+    while !line.is_char_boundary(end) {
+        end += 1;
+    }
+    while end < line.len() && continues_grapheme(line, end) {
+        end += line[end..].chars().next().map_or(1, char::len_utf8);
+    }
+
+    (start, end)
+}
+
+/// Snaps a match span with `snap_span` and returns it as byte offsets and
+/// character offsets: `(start, end, char_start, char_end)`.
+pub fn char_span(line: &str, start: usize, end: usize) -> (usize, usize, usize, usize) {
+    let (start, end) = snap_span(line, start, end);
+    let char_start = line[..start].chars().count();
+    (start, end, char_start, char_start + line[start..end].chars().count())
+}
+
+/// Whether the character at `index` attaches to the one before it.
+fn continues_grapheme(line: &str, index: usize) -> bool {
+    let Some(c) = line[index..].chars().next() else { return false };
+    let after_joiner = line[..index].ends_with(ZERO_WIDTH_JOINER);
+    after_joiner || c == ZERO_WIDTH_JOINER || (c.width() == Some(0) && !c.is_control())
+}
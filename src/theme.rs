@@ -0,0 +1,256 @@
+use std::env;
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use colored::{Color, ColoredString, Colorize};
+
+/// Colors used for every piece of styled output. `None` leaves that piece
+/// in the terminal's default color.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub path: Option<Color>,
+    pub line_number: Option<Color>,
+    pub match_fg: Option<Color>,
+    /// Without a background color, matches are shown in reverse video.
+    pub match_bg: Option<Color>,
+    /// Backgrounds for the matches of the second, third, ... `--term`,
+    /// cycled when there are more terms. Empty paints every term alike.
+    pub term_bgs: Vec<Color>,
+    pub count: Option<Color>,
+    pub notice: Option<Color>,
+    pub keyword: Option<Color>,
+    pub comment: Option<Color>,
+    pub string: Option<Color>,
+    pub number: Option<Color>,
+}
+
+/// Built-in `--theme` presets.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Theme {
+    Dark,
+    Light,
+    Solarized,
+    /// No colors; matches in reverse video
+    Mono,
+}
+
+impl Palette {
+    pub fn for_theme(theme: Theme) -> Palette {
+        match theme {
+            Theme::Dark => Palette::dark(),
+            Theme::Light => Palette::light(),
+            Theme::Solarized => Palette::solarized(),
+            Theme::Mono => Palette::mono(),
+        }
+    }
+
+    pub fn dark() -> Palette {
+        Palette {
+            path: Some(Color::Blue),
+            line_number: Some(Color::Yellow),
+            match_fg: None,
+            match_bg: Some(Color::Yellow),
+            term_bgs: vec![Color::Cyan, Color::Magenta, Color::Green, Color::Blue],
+            count: Some(Color::Green),
+            notice: Some(Color::Yellow),
+            keyword: Some(Color::Magenta),
+            comment: Some(Color::BrightBlack),
+            string: Some(Color::Green),
+            number: Some(Color::Cyan),
+        }
+    }
+
+    /// Avoids yellow/bright foregrounds, which wash out on light backgrounds.
+    pub fn light() -> Palette {
+        Palette {
+            path: Some(Color::Blue),
+            line_number: Some(Color::Magenta),
+            match_fg: Some(Color::Black),
+            match_bg: Some(Color::BrightYellow),
+            term_bgs: vec![Color::BrightCyan, Color::BrightMagenta, Color::BrightGreen, Color::BrightBlue],
+            count: Some(Color::Green),
+            notice: Some(Color::Red),
+            keyword: Some(Color::Blue),
+            comment: Some(Color::BrightBlack),
+            string: Some(Color::Green),
+            number: Some(Color::Red),
+        }
+    }
+
+    /// Ethan Schoonover's Solarized accents, readable on both of its
+    /// backgrounds.
+    pub fn solarized() -> Palette {
+        let color = |r, g, b| Color::TrueColor { r, g, b };
+        let rgb = |r, g, b| Some(color(r, g, b));
+        Palette {
+            path: rgb(0x26, 0x8b, 0xd2),
+            line_number: rgb(0xb5, 0x89, 0x00),
+            match_fg: rgb(0xfd, 0xf6, 0xe3),
+            match_bg: rgb(0xcb, 0x4b, 0x16),
+            term_bgs: vec![color(0x26, 0x8b, 0xd2), color(0xd3, 0x36, 0x82), color(0x85, 0x99, 0x00), color(0x6c, 0x71, 0xc4)],
+            count: rgb(0x85, 0x99, 0x00),
+            notice: rgb(0xb5, 0x89, 0x00),
+            keyword: rgb(0x6c, 0x71, 0xc4),
+            comment: rgb(0x93, 0xa1, 0xa1),
+            string: rgb(0x2a, 0xa1, 0x98),
+            number: rgb(0xd3, 0x36, 0x82),
+        }
+    }
+
+    pub fn mono() -> Palette {
+        Palette {
+            path: None,
+            line_number: None,
+            match_fg: None,
+            match_bg: None,
+            term_bgs: Vec::new(),
+            count: None,
+            notice: None,
+            keyword: None,
+            comment: None,
+            string: None,
+            number: None,
+        }
+    }
+
+    pub fn paint_match(&self, text: ColoredString) -> ColoredString {
+        match self.match_bg {
+            Some(bg) => text.themed(self.match_fg).on_color(bg),
+            None => text.themed(self.match_fg).reversed(),
+        }
+    }
+
+    /// Paints a match of `--term` number `term` (from 0): the first term in
+    /// the match colors, later ones in the `term_bgs` cycle.
+    pub fn paint_term(&self, text: ColoredString, term: usize) -> ColoredString {
+        match term {
+            0 => self.paint_match(text),
+            _ if self.term_bgs.is_empty() => self.paint_match(text),
+            _ => text.themed(self.match_fg).on_color(self.term_bgs[(term - 1) % self.term_bgs.len()]),
+        }
+    }
+}
+
+/// Applies an optional palette color.
+pub trait Themed {
+    fn themed(self, color: Option<Color>) -> ColoredString;
+}
+
+impl<T: Colorize> Themed for T {
+    fn themed(self, color: Option<Color>) -> ColoredString {
+        match color {
+            Some(color) => self.color(color),
+            None => self.normal(),
+        }
+    }
+}
+
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+/// The palette chosen at startup (dark until `init` runs).
+pub fn palette() -> &'static Palette {
+    PALETTE.get_or_init(Palette::dark)
+}
+
+pub fn init(palette: Palette) {
+    let _ = PALETTE.set(palette);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Background {
+    /// Detect from the terminal
+    Auto,
+    Dark,
+    Light,
+}
+
+/// Resolves `Auto` by asking the terminal, defaulting to dark when it can't
+/// tell.
+pub fn detect_background(requested: Background) -> Background {
+    if requested != Background::Auto {
+        return requested;
+    }
+    from_colorfgbg()
+        .or_else(query_terminal)
+        .unwrap_or(Background::Dark)
+}
+
+/// `COLORFGBG` is set by rxvt, Konsole and others as `fg;bg` (sometimes
+/// `fg;default;bg`) using the 16 ANSI color indexes.
+fn from_colorfgbg() -> Option<Background> {
+    let value = env::var("COLORFGBG").ok()?;
+    let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(if bg == 7 || bg >= 9 { Background::Light } else { Background::Dark })
+}
+
+/// Sends the OSC 11 background-color query and parses an
+/// `rgb:RRRR/GGGG/BBBB` reply. Terminals that don't answer time out quickly.
+#[cfg(unix)]
+fn query_terminal() -> Option<Background> {
+    use std::fs::OpenOptions;
+    use std::io::{IsTerminal, Read, Write};
+    use std::os::unix::io::AsRawFd;
+
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let fd = tty.as_raw_fd();
+
+    // SAFETY: termios is plain data, and the original settings are restored
+    // before returning.
+    let original = unsafe {
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return None;
+        }
+        let mut raw = termios;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 1; // tenths of a second per read
+        libc::tcsetattr(fd, libc::TCSANOW, &raw);
+        termios
+    };
+
+    let mut reply = Vec::new();
+    if tty.write_all(b"\x1b]11;?\x07").is_ok() {
+        let mut buf = [0u8; 64];
+        while let Ok(n) = tty.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            reply.extend_from_slice(&buf[..n]);
+            if reply.ends_with(b"\x07") || reply.ends_with(b"\x1b\\") || reply.len() > 64 {
+                break;
+            }
+        }
+    }
+    unsafe {
+        libc::tcsetattr(fd, libc::TCSANOW, &original);
+    }
+
+    parse_osc11(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(not(unix))]
+fn query_terminal() -> Option<Background> {
+    None
+}
+
+fn parse_osc11(reply: &str) -> Option<Background> {
+    let rgb = reply.split("rgb:").nth(1)?;
+    let channels: Vec<f64> = rgb
+        .split(['/', '\x07', '\x1b'])
+        .take(3)
+        .map(|c| {
+            let max = 16f64.powi(c.len() as i32) - 1.0;
+            u32::from_str_radix(c, 16).map(|v| v as f64 / max)
+        })
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if channels.len() != 3 {
+        return None;
+    }
+    let luminance = 0.2126 * channels[0] + 0.7152 * channels[1] + 0.0722 * channels[2];
+    Some(if luminance > 0.5 { Background::Light } else { Background::Dark })
+}
@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use colored::Colorize;
+use regex::Regex;
+
+use crate::git;
+use crate::json;
+use crate::theme::{self, Themed};
+use crate::SearchResult;
+
+/// The search `fsearch todos` runs: an annotation tag as a whole word.
+pub const PATTERN: &str = r"\b(?:TODO|FIXME|HACK|XXX)\b";
+
+/// The flags `fsearch todos` stands for: a search for `PATTERN` in comments.
+pub const FLAGS: [&str; 6] = ["-t", PATTERN, "--regex", "--case-sensitive", "--scope", "comments"];
+
+/// How `fsearch todos` groups its report.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TodoGroup {
+    /// One group per file
+    File,
+    /// One group per author of the annotated line, from git blame
+    Author,
+    /// One group per tag (TODO, FIXME, ...)
+    Tag,
+}
+
+/// How `fsearch todos` writes its report.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TodoFormat {
+    /// Indented text for people
+    Text,
+    /// A JSON object mapping each group to its annotations
+    Json,
+    /// A Markdown section per group with a bullet per annotation
+    Markdown,
+}
+
+/// One annotation found in a comment, such as
+/// `TODO(alice): handle retries, see PROJ-12`.
+pub struct Todo {
+    pub path: String,
+    pub line: usize,
+    pub tag: String,
+    /// From `TAG(name)` or an `@name` in the text
+    pub assignee: Option<String>,
+    /// A ticket ID like `PROJ-12` or `#123`, in the parentheses or the text
+    pub ticket: Option<String>,
+    pub text: String,
+    /// Filled in when grouping by author
+    pub author: Option<String>,
+}
+
+fn annotation() -> &'static Regex {
+    static ANNOTATION: OnceLock<Regex> = OnceLock::new();
+    ANNOTATION.get_or_init(|| {
+        Regex::new(r"\b(?P<tag>TODO|FIXME|HACK|XXX)\b(?:\((?P<inner>[^)]*)\))?\s*[:\-]?\s*(?P<text>.*)").unwrap()
+    })
+}
+
+fn ticket() -> &'static Regex {
+    static TICKET: OnceLock<Regex> = OnceLock::new();
+    TICKET.get_or_init(|| Regex::new(r"\b[A-Z][A-Z0-9]+-\d+\b|#\d+\b").unwrap())
+}
+
+fn mention() -> &'static Regex {
+    static MENTION: OnceLock<Regex> = OnceLock::new();
+    MENTION.get_or_init(|| Regex::new(r"(?:^|\s)@(?P<name>[\w.-]+)").unwrap())
+}
+
+/// Parses the annotations out of one file's results.
+pub fn parse(results: &[SearchResult]) -> Vec<Todo> {
+    let mut todos = Vec::new();
+    for result in results {
+        let Some(caps) = annotation().captures(&result.line) else { continue };
+        let inner = caps.name("inner").map(|m| m.as_str().trim()).filter(|inner| !inner.is_empty());
+        let text = caps["text"].trim().trim_end_matches("*/").trim_end().to_string();
+        let inner_ticket = inner.and_then(|inner| ticket().find(inner)).map(|m| m.as_str().to_string());
+        let assignee = inner
+            .filter(|_| inner_ticket.is_none())
+            .map(|inner| inner.trim_start_matches('@').to_string())
+            .or_else(|| mention().captures(&text).map(|caps| caps["name"].to_string()));
+        let ticket = inner_ticket.or_else(|| ticket().find(&text).map(|m| m.as_str().to_string()));
+        todos.push(Todo {
+            path: result.file_path.clone(),
+            line: result.line_number,
+            tag: caps["tag"].to_string(),
+            assignee,
+            ticket,
+            text,
+            author: None,
+        });
+    }
+    todos
+}
+
+/// Looks up the author of each annotation with git blame, one run per
+/// file. Files git can't blame get "unknown".
+pub fn attribute(todos: &mut [Todo]) {
+    let mut blames: HashMap<String, Option<HashMap<usize, String>>> = HashMap::new();
+    for todo in todos {
+        let authors = blames.entry(todo.path.clone()).or_insert_with(|| git::blame_authors(&todo.path).ok());
+        let author = authors.as_ref().and_then(|authors| authors.get(&todo.line));
+        todo.author = Some(author.cloned().unwrap_or_else(|| "unknown".to_string()));
+    }
+}
+
+/// Renders the report of `todos`, grouped `by` and sorted by group name.
+pub fn report(todos: &[Todo], by: TodoGroup, format: TodoFormat) -> String {
+    let mut groups: BTreeMap<&str, Vec<&Todo>> = BTreeMap::new();
+    for todo in todos {
+        let key = match by {
+            TodoGroup::File => todo.path.as_str(),
+            TodoGroup::Author => todo.author.as_deref().unwrap_or("unknown"),
+            TodoGroup::Tag => todo.tag.as_str(),
+        };
+        groups.entry(key).or_default().push(todo);
+    }
+    match format {
+        TodoFormat::Text => text(&groups, by, todos.len()),
+        TodoFormat::Json => json(&groups),
+        TodoFormat::Markdown => markdown(&groups, by),
+    }
+}
+
+/// Where an annotation is, leaving out what the group heading already says.
+fn location(todo: &Todo, by: TodoGroup) -> String {
+    match by {
+        TodoGroup::File => todo.line.to_string(),
+        _ => format!("{}:{}", todo.path, todo.line),
+    }
+}
+
+/// The assignee and ticket, as `[@alice PROJ-12]`, or nothing.
+fn details(todo: &Todo) -> String {
+    let parts: Vec<String> = todo
+        .assignee
+        .iter()
+        .map(|assignee| format!("@{}", assignee))
+        .chain(todo.ticket.iter().cloned())
+        .collect();
+    match parts.is_empty() {
+        true => String::new(),
+        false => format!("[{}] ", parts.join(" ")),
+    }
+}
+
+fn text(groups: &BTreeMap<&str, Vec<&Todo>>, by: TodoGroup, total: usize) -> String {
+    let palette = theme::palette();
+    let mut out = String::new();
+    for (key, todos) in groups {
+        let _ = writeln!(out, "{} ({})", key.themed(palette.path).bold(), todos.len());
+        for todo in todos {
+            let tag = if by == TodoGroup::Tag { String::new() } else { format!("{} ", todo.tag.themed(palette.keyword)) };
+            let _ = writeln!(out, "  {}  {}{}{}", location(todo, by).themed(palette.line_number), tag, details(todo), todo.text);
+        }
+    }
+    let _ = writeln!(out, "\n{}", format!("{} annotations in {} groups", total, groups.len()).themed(palette.count));
+    out
+}
+
+fn json(groups: &BTreeMap<&str, Vec<&Todo>>) -> String {
+    let optional = |value: &Option<String>| value.as_deref().map_or("null".to_string(), json::string);
+    let groups: Vec<String> = groups
+        .iter()
+        .map(|(key, todos)| {
+            let todos: Vec<String> = todos
+                .iter()
+                .map(|todo| {
+                    format!(
+                        "{{\"path\":{},\"line\":{},\"tag\":{},\"assignee\":{},\"ticket\":{},\"author\":{},\"text\":{}}}",
+                        json::string(&todo.path),
+                        todo.line,
+                        json::string(&todo.tag),
+                        optional(&todo.assignee),
+                        optional(&todo.ticket),
+                        optional(&todo.author),
+                        json::string(&todo.text),
+                    )
+                })
+                .collect();
+            format!("{}:[{}]", json::string(key), todos.join(","))
+        })
+        .collect();
+    format!("{{{}}}\n", groups.join(","))
+}
+
+fn markdown(groups: &BTreeMap<&str, Vec<&Todo>>, by: TodoGroup) -> String {
+    let mut out = String::from("# TODO report\n");
+    for (key, todos) in groups {
+        let _ = write!(out, "\n## {} ({})\n\n", key, todos.len());
+        for todo in todos {
+            let tag = if by == TodoGroup::Tag { String::new() } else { format!("**{}** ", todo.tag) };
+            let _ = writeln!(out, "- `{}` {}{}{}", location(todo, by), tag, details(todo), todo.text);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{FileProfile, Profiler};
+    use crate::{pattern, Args};
+    use clap::Parser;
+
+    fn todos(name: &str, content: &str) -> Vec<Todo> {
+        let path = std::env::temp_dir().join(format!("fsearch-test-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        let path = path.to_str().unwrap();
+        let args = Args::parse_from(["fsearch"].iter().chain(&FLAGS));
+        let pattern = pattern::compile(&args).unwrap();
+        let results = crate::search_in_file(path, &pattern, &args, &Profiler::new(false), &mut FileProfile::default());
+        std::fs::remove_file(path).unwrap();
+        parse(&results.unwrap())
+    }
+
+    #[test]
+    fn only_comments_are_annotations() {
+        let found = todos("strings.rs", "let s = \"TODO list\";\n// FIXME(PROJ-12): retry @alice\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].line, found[0].tag.as_str(), found[0].text.as_str()), (2, "FIXME", "retry @alice"));
+        assert_eq!((found[0].ticket.as_deref(), found[0].assignee.as_deref()), (Some("PROJ-12"), Some("alice")));
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt::Write;
+
+use crate::glob::{parse_glob, Glob};
+
+/// The built-in file types for `--type`, by name.
+const BUILTIN: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"]),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("docker", &["Dockerfile", "Dockerfile.*", "*.dockerfile"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("notebook", &["*.ipynb"]),
+    ("python", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    (
+        "web",
+        &["*.html", "*.htm", "*.css", "*.scss", "*.sass", "*.less", "*.js", "*.mjs", "*.jsx", "*.ts", "*.tsx", "*.vue", "*.svelte"],
+    ),
+    ("xml", &["*.xml", "*.xsd", "*.xsl", "*.svg"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// A `--type-add` definition: globs to add to a new or built-in type.
+#[derive(Debug, Clone)]
+pub struct TypeAdd {
+    pub name: String,
+    pub globs: Vec<String>,
+}
+
+/// Parses `NAME:GLOB[,GLOB...]`, e.g. `web:*.vue,*.astro`.
+pub fn parse_type_add(spec: &str) -> Result<TypeAdd, String> {
+    let Some((name, globs)) = spec.split_once(':') else {
+        return Err(format!("'{}' is not NAME:GLOB[,GLOB...]", spec));
+    };
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("'{}' is not a valid type name", name));
+    }
+    let globs: Vec<String> = globs.split(',').map(str::trim).filter(|glob| !glob.is_empty()).map(String::from).collect();
+    if globs.is_empty() {
+        return Err(format!("type '{}' needs at least one glob", name));
+    }
+    for glob in &globs {
+        parse_glob(glob)?;
+    }
+    Ok(TypeAdd { name: name.to_string(), globs })
+}
+
+/// Every type with its globs, built-in ones extended by `added`, by name.
+fn definitions(added: &[TypeAdd]) -> Vec<(String, Vec<String>)> {
+    let mut types: Vec<(String, Vec<String>)> = BUILTIN
+        .iter()
+        .map(|(name, globs)| (name.to_string(), globs.iter().map(|g| g.to_string()).collect()))
+        .collect();
+    for add in added {
+        match types.iter_mut().find(|(name, _)| *name == add.name) {
+            Some((_, globs)) => globs.extend(add.globs.iter().cloned()),
+            None => types.push((add.name.clone(), add.globs.clone())),
+        }
+    }
+    types.sort_by(|a, b| a.0.cmp(&b.0));
+    types
+}
+
+/// The globs of the types `names`, or an error naming one that isn't defined.
+pub fn globs(names: &[String], added: &[TypeAdd]) -> Result<Vec<Glob>, String> {
+    let types = definitions(added);
+    let mut out = Vec::new();
+    for name in names {
+        let Some((_, globs)) = types.iter().find(|(known, _)| known == name) else {
+            return Err(format!("unknown file type '{}' (see fsearch --type-list)", name));
+        };
+        for glob in globs {
+            out.push(parse_glob(glob)?);
+        }
+    }
+    Ok(out)
+}
+
+/// The `--type-list` listing: one `name: globs` line per type.
+pub fn list(added: &[TypeAdd]) -> String {
+    let mut out = String::new();
+    for (name, globs) in definitions(added) {
+        let _ = writeln!(out, "{}: {}", name, globs.join(", "));
+    }
+    out
+}
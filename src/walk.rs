@@ -0,0 +1,368 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+use crate::glob::Glob;
+use crate::ignore::Ignores;
+
+/// Directory names skipped by default: dependency and build trees that are
+/// rarely what a search is after, whether or not a .gitignore says so.
+const VENDORED_DIRS: &[&str] = &["vendor", "third_party", "node_modules", ".venv", "target"];
+
+/// Which files a walk collects.
+pub struct WalkOptions<'a> {
+    /// Only collect files with one of these extensions (lowercase, no
+    /// leading dot, possibly multi-part like 'd.ts'); empty collects every file
+    pub extensions: &'a [String],
+    /// Descend into subdirectories
+    pub recursive: bool,
+    /// Descend into symlinked directories too, skipping links back into a
+    /// directory that is already being walked
+    pub follow: bool,
+    /// When recursing, descend at most this many levels below the root
+    pub max_depth: Option<usize>,
+    /// Descend into directories from `VENDORED_DIRS` too
+    pub include_vendored: bool,
+    /// When not empty, only collect files matching one of these
+    pub include: &'a [Glob],
+    /// When not empty, only collect files matching one of these too
+    pub types: &'a [Glob],
+    /// Skip files and directories matching any of these; exclusions take
+    /// precedence over every inclusion rule
+    pub exclude: &'a [Glob],
+    /// Fail on the first unreadable directory instead of skipping it
+    pub strict: bool,
+    /// Skip what `.gitignore` files, `.git/info/exclude` and the global git
+    /// excludes file ignore
+    pub ignore: bool,
+    /// Collect hidden files and descend into hidden directories too, other
+    /// than `.git`
+    pub hidden: bool,
+}
+
+/// A directory the walk couldn't read and skipped.
+pub type Skipped = (PathBuf, io::Error);
+
+impl WalkOptions<'_> {
+    fn excludes_file(&self, relative: &Path) -> bool {
+        let matches_any = |globs: &[Glob]| globs.is_empty() || globs.iter().any(|glob| glob.matches_file(relative));
+        let included = matches_any(self.include) && matches_any(self.types);
+        !included || self.exclude.iter().any(|glob| glob.matches_file(relative))
+    }
+
+    fn excludes_dir(&self, relative: &Path) -> bool {
+        (!self.include_vendored && is_vendored(relative))
+            || self.exclude.iter().any(|glob| glob.matches_dir(relative))
+    }
+}
+
+/// Whether `path` is hidden: a dotfile or dot-directory, or on Windows one
+/// with the hidden attribute.
+fn is_hidden(path: &Path) -> bool {
+    if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if fs::symlink_metadata(path).is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0) {
+            return true;
+        }
+    }
+    false
+}
+
+fn is_vendored(dir: &Path) -> bool {
+    dir.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| VENDORED_DIRS.contains(&name))
+}
+
+/// Normalizes one `--extension` value: a leading dot is dropped and case is
+/// folded, since extensions are matched case-insensitively.
+pub fn parse_extension(value: &str) -> Result<String, String> {
+    let ext = value.trim().strip_prefix('.').unwrap_or(value.trim());
+    if ext.is_empty() {
+        return Err("empty extension".to_string());
+    }
+    if ext.contains(['/', '\\', '*', '?']) {
+        return Err(format!("'{}' is not a file extension", value));
+    }
+    Ok(ext.to_lowercase())
+}
+
+fn should_search_file(path: &Path, extensions: &[String]) -> bool {
+    if extensions.is_empty() {
+        return path.is_file();
+    }
+    // Compare against the end of the name rather than `Path::extension`, so
+    // multi-part extensions such as 'd.ts' or 'tar.gz' work too. A dotfile's
+    // whole name is not an extension.
+    let Some(name) = path.file_name() else { return false };
+    let name = name.to_string_lossy().to_lowercase();
+    extensions.iter().any(|ext| {
+        name.len() > ext.len() + 1 && name.ends_with(ext.as_str()) && name[..name.len() - ext.len()].ends_with('.')
+    })
+}
+
+/// Collects the files under `dir` to search. Subdirectories are read in
+/// parallel on the current rayon pool, but the result keeps the same order
+/// a sequential depth-first walk would produce. `dir` itself is always
+/// walked, even when it is a vendored directory.
+///
+/// Subdirectories that can't be read are skipped and returned, unless
+/// `options.strict` is set; an unreadable `dir` is always an error.
+pub fn collect_files(dir: &Path, options: &WalkOptions, files: &mut Vec<String>) -> io::Result<Vec<Skipped>> {
+    let skipped = Mutex::new(Vec::new());
+    let ignores = if options.ignore { Ignores::for_root(dir) } else { Ignores::default() };
+    files.extend(collect_dir(dir, dir, 0, &[], &ignores, options, &skipped)?);
+    Ok(skipped.into_inner().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// `depth` is how many levels `dir` is below `root`. When following
+/// symlinks, `above` holds the canonical paths of the directories from the
+/// root down to `dir`'s parent, so a link back into one of them is caught
+/// instead of recursing forever.
+fn collect_dir(
+    root: &Path,
+    dir: &Path,
+    depth: usize,
+    above: &[PathBuf],
+    ignores: &Ignores,
+    options: &WalkOptions,
+    skipped: &Mutex<Vec<Skipped>>,
+) -> io::Result<Vec<String>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut chain = Vec::new();
+    if options.follow {
+        let canonical = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        if above.contains(&canonical) {
+            return Ok(Vec::new());
+        }
+        chain = above.to_vec();
+        chain.push(canonical);
+    }
+    let ignores = match options.ignore {
+        true => ignores.enter(dir, dir.strip_prefix(root).unwrap_or(dir)),
+        false => Ignores::default(),
+    };
+
+    let entries = fs::read_dir(dir).and_then(|entries| {
+        entries
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()
+    });
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) if !options.strict && dir != root => {
+            skipped.lock().unwrap_or_else(|e| e.into_inner()).push((dir.to_path_buf(), e));
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", dir.display(), e))),
+    };
+
+    let nested = entries
+        .par_iter()
+        .map(|path| {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            // Git's own files are never searched, even with --hidden, unless
+            // `.git` is the search directory itself.
+            if (!options.hidden && is_hidden(path)) || path.file_name().is_some_and(|name| name == ".git") {
+                Ok(Vec::new())
+            } else if path.is_file()
+                && should_search_file(path, options.extensions)
+                && !options.excludes_file(relative)
+                && !ignores.is_ignored(relative, false)
+            {
+                Ok(path.to_str().map(|p| vec![p.to_string()]).unwrap_or_default())
+            } else if options.recursive
+                && options.max_depth.is_none_or(|max| depth < max)
+                && path.is_dir()
+                && (options.follow || !path.is_symlink())
+                && !options.excludes_dir(relative)
+                && !ignores.is_ignored(relative, true)
+            {
+                collect_dir(root, path, depth + 1, &chain, &ignores, options, skipped)
+            } else {
+                Ok(Vec::new())
+            }
+        })
+        .collect::<io::Result<Vec<Vec<String>>>>()?;
+
+    Ok(nested.into_iter().flatten().collect())
+}
+
+/// Drops files that resolve to the same canonical path as an earlier entry,
+/// e.g. a symlink and its target both inside the search root, so each
+/// physical file is searched and reported once.
+pub fn dedup_canonical(files: &mut Vec<String>) {
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    files.retain(|path| match fs::canonicalize(path) {
+        Ok(canonical) => seen.insert(canonical),
+        Err(_) => true,
+    });
+}
+
+/// Drops paths that are hard links to an earlier file (same device and
+/// inode), so shared content is searched once. Returns the dropped paths
+/// keyed by the index of the file they link to in the updated `files`.
+#[cfg(unix)]
+pub fn dedup_hard_links(files: &mut Vec<String>) -> HashMap<usize, Vec<String>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let ids: Vec<Option<(u64, u64)>> = files
+        .par_iter()
+        .map(|path| fs::metadata(path).ok().filter(|m| m.nlink() > 1).map(|m| (m.dev(), m.ino())))
+        .collect();
+
+    let mut first: HashMap<(u64, u64), usize> = HashMap::new();
+    let mut links: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut kept = Vec::with_capacity(files.len());
+    for (path, id) in files.drain(..).zip(ids) {
+        match id.map(|id| (id, first.get(&id).copied())) {
+            Some((_, Some(index))) => links.entry(index).or_default().push(path),
+            Some((id, None)) => {
+                first.insert(id, kept.len());
+                kept.push(path);
+            }
+            None => kept.push(path),
+        }
+    }
+    *files = kept;
+    links
+}
+
+/// std has no stable way to read file IDs elsewhere, so hard links are
+/// searched once per path.
+#[cfg(not(unix))]
+pub fn dedup_hard_links(_files: &mut Vec<String>) -> HashMap<usize, Vec<String>> {
+    HashMap::new()
+}
+
+/// The order files are handed to the searchers, which is also the order
+/// their results are printed in.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum WalkOrder {
+    /// Depth-first, as directories are read
+    Dfs,
+    /// Breadth-first: shallow files before deeper ones
+    Bfs,
+    /// Largest files first, which keeps all threads busy until the end
+    Size,
+    /// Most recently modified files first
+    Mtime,
+}
+
+/// Reorders `files` for `order`. Sorts are stable, so files that tie keep
+/// their depth-first order; files whose metadata can't be read go last.
+pub fn schedule(files: &mut Vec<String>, order: WalkOrder) {
+    match order {
+        WalkOrder::Dfs => {}
+        WalkOrder::Bfs => files.sort_by_cached_key(|path| Path::new(path).components().count()),
+        WalkOrder::Size => sort_by_metadata(files, |meta| Reverse(meta.len())),
+        WalkOrder::Mtime => sort_by_metadata(files, |meta| Reverse(meta.modified().ok())),
+    }
+}
+
+fn sort_by_metadata<K: Ord + Send>(files: &mut Vec<String>, key: impl Fn(&fs::Metadata) -> K + Sync) {
+    let keys: Vec<Option<K>> = files.par_iter().map(|path| fs::metadata(path).ok().map(|meta| key(&meta))).collect();
+    let mut keyed: Vec<_> = keys.into_iter().zip(files.drain(..)).collect();
+    keyed.sort_by(|(a, _), (b, _)| (a.is_none(), a).cmp(&(b.is_none(), b)));
+    files.extend(keyed.into_iter().map(|(_, path)| path));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fsearch-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn options() -> WalkOptions<'static> {
+        WalkOptions {
+            extensions: &[],
+            recursive: true,
+            follow: false,
+            max_depth: None,
+            include_vendored: false,
+            include: &[],
+            types: &[],
+            exclude: &[],
+            strict: false,
+            ignore: false,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn parallel_walk_keeps_depth_first_order() {
+        let dir = temp_dir("walk-order");
+        for name in ["b/2.txt", "b/1/x.txt", "a.txt", "c/d/e.txt", "z.txt"] {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x").unwrap();
+        }
+
+        // Several threads, so sibling directories really are read in parallel.
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let collect = || {
+            let mut files = Vec::new();
+            pool.install(|| collect_files(&dir, &options(), &mut files)).unwrap();
+            files
+        };
+        let first = collect();
+        let again = collect();
+        let expected = sequential(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first.len(), 5);
+        assert_eq!(first, expected);
+        assert_eq!(again, first);
+    }
+
+    /// The order a plain single-threaded depth-first walk produces.
+    fn sequential(dir: &Path) -> Vec<String> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(sequential(&path));
+            } else {
+                files.push(path.to_str().unwrap().to_string());
+            }
+        }
+        files
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_to_a_listed_file_are_dropped() {
+        let dir = temp_dir("dedup");
+        let target = dir.join("target.txt");
+        let link = dir.join("link.txt");
+        let other = dir.join("other.txt");
+        fs::write(&target, "x").unwrap();
+        fs::write(&other, "y").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let path = |p: &Path| p.to_str().unwrap().to_string();
+        let missing = path(&dir.join("missing.txt"));
+
+        let mut files = vec![path(&link), path(&other), path(&target), missing.clone()];
+        dedup_canonical(&mut files);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, vec![path(&link), path(&other), missing]);
+    }
+}